@@ -0,0 +1,473 @@
+//! `cargo ts-rpc call`: calls a procedure of a running server, building the positional request
+//! body from named arguments with the help of the api manifest.
+//!
+//! ```text
+//! cargo ts-rpc call login --arg email=me@example.com --arg password=@password.json
+//! echo '{"procedure": "login", "args": {"email": "me@example.com"}}' | cargo ts-rpc call --batch
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use serde_json::Value;
+use ts_rpc_core::export::{ExportConfig, DEFAULT_CONFIG};
+use ts_rpc_core::manifest::{Manifest, ParamSource, Procedure};
+use ts_rpc_core::ts_type::TsType;
+
+// `ts_rpc_core::introspection::SCHEMA_PATH`, which would pull in axum
+const SCHEMA_PATH: &str = "/__ts_rpc/schema";
+
+#[derive(Default)]
+struct Args {
+    procedure: Option<String>,
+    // name -> value as given on the command line, files already read
+    args: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    server: Option<String>,
+    config: Option<PathBuf>,
+    // manifest file used instead of the schema served by the server
+    schema: Option<PathBuf>,
+    batch: bool,
+}
+
+/// Where and how to send requests.
+struct Server {
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+/// The arguments of a call: the positional request body and, as text, the values of the
+/// parameters read from headers, cookies or the query string.
+#[derive(Debug, Default, PartialEq)]
+struct Request {
+    body: Value,
+    headers: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> ExitCode {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n{}", crate::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let result = server(&args).and_then(|server| {
+        let manifest = manifest(&args, &server)?;
+        if args.batch {
+            Ok(batch(&server, &manifest))
+        } else {
+            let procedure = args.procedure.as_deref().unwrap();
+            let values = args
+                .args
+                .iter()
+                .map(|(name, value)| {
+                    let ty = param_type(&manifest, procedure, name)?;
+                    Ok((name.clone(), parse_value(name, value, ty)?))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let request = request(&manifest, procedure, values)?;
+            let (ok, response) = call(&server, &manifest, procedure, &request)?;
+            println!("{}", serde_json::to_string_pretty(&response).unwrap());
+            Ok(ok)
+        }
+    });
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut iter: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = Args::default();
+    while let Some(arg) = iter.next() {
+        let mut value = |flag: &str| iter.next().ok_or(format!("`{flag}` requires a value"));
+        match arg.as_str() {
+            "--arg" | "-a" => {
+                let arg = value(&arg)?;
+                let (name, value) = arg
+                    .split_once('=')
+                    .ok_or(format!("expected `name=value`, got `{arg}`"))?;
+                let value = match value.strip_prefix('@') {
+                    Some(path) => std::fs::read_to_string(path)
+                        .map_err(|e| format!("failed to read `{path}`: {e}"))?,
+                    None => value.to_string(),
+                };
+                args.args.push((name.to_string(), value));
+            }
+            "--header" | "-H" => {
+                let header = value(&arg)?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or(format!("expected `name: value`, got `{header}`"))?;
+                args.headers
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            }
+            "--server" | "-s" => args.server = Some(value(&arg)?),
+            "--config" => args.config = Some(PathBuf::from(value(&arg)?)),
+            "--schema" => args.schema = Some(PathBuf::from(value(&arg)?)),
+            "--batch" => args.batch = true,
+            _ if arg.starts_with('-') => return Err(format!("unexpected argument `{arg}`")),
+            _ if args.procedure.is_none() => args.procedure = Some(arg),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    match (&args.procedure, args.batch) {
+        (None, false) => Err("expected a procedure name".to_string()),
+        (Some(_), true) => Err("`--batch` reads the procedures from stdin".to_string()),
+        _ if args.batch && !args.args.is_empty() => {
+            Err("`--arg` can't be combined with `--batch`".to_string())
+        }
+        _ => Ok(args),
+    }
+}
+
+/// The server to call: a url, a server from the config or the config's `url`.
+fn server(args: &Args) -> Result<Server, String> {
+    let config_path = args.config.clone().unwrap_or(PathBuf::from(DEFAULT_CONFIG));
+    let config = match ExportConfig::load(&config_path) {
+        Ok(config) => Some(config),
+        // The config is optional when passing a url.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && args.config.is_none() => None,
+        Err(e) => return Err(format!("{}: {e}", config_path.display())),
+    };
+
+    let mut server = match (&args.server, &config) {
+        (Some(server), _) if server.contains("://") => Server {
+            url: server.clone(),
+            headers: Vec::new(),
+        },
+        (Some(name), Some(config)) => {
+            let server = config
+                .servers
+                .get(name)
+                .ok_or(format!("no server `{name}` configured"))?;
+            Server {
+                url: server.url.clone(),
+                headers: server
+                    .headers
+                    .iter()
+                    .map(|(name, value)| Ok((name.clone(), expand_env(value)?)))
+                    .collect::<Result<_, String>>()?,
+            }
+        }
+        (Some(name), None) => {
+            return Err(format!(
+                "no server `{name}` configured, `{DEFAULT_CONFIG}` not found"
+            ))
+        }
+        (None, config) => Server {
+            url: config
+                .as_ref()
+                .and_then(|c| c.url.clone())
+                .ok_or("no server given, pass `--server <name | url>`")?,
+            headers: Vec::new(),
+        },
+    };
+    server.url = server.url.trim_end_matches('/').to_string();
+    server.headers.extend(args.headers.iter().cloned());
+    Ok(server)
+}
+
+/// Replaces `${VAR}` with the environment variable `VAR`.
+fn expand_env(value: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or(format!("unclosed `${{` in `{value}`"))?;
+        let var = &rest[start + 2..start + end];
+        let var_value =
+            std::env::var(var).map_err(|_| format!("environment variable `{var}` is not set"))?;
+        expanded += &rest[..start];
+        expanded += &var_value;
+        rest = &rest[start + end + 1..];
+    }
+    Ok(expanded + rest)
+}
+
+fn manifest(args: &Args, server: &Server) -> Result<Manifest, String> {
+    let json = match &args.schema {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read `{}`: {e}", path.display()))?,
+        None => {
+            let url = format!("{}{SCHEMA_PATH}", server.url);
+            let mut request = ureq::get(&url);
+            for (name, value) in server.headers.iter() {
+                request = request.set(name, value);
+            }
+            let error = |e: &dyn std::fmt::Display| {
+                format!(
+                    "failed to fetch the schema from `{url}`, is `Api::introspection` enabled? \
+                     Otherwise pass a manifest with `--schema`. {e}"
+                )
+            };
+            request
+                .call()
+                .map_err(|e| error(&e))?
+                .into_string()
+                .map_err(|e| error(&e))?
+        }
+    };
+    Manifest::from_json(&json).map_err(|e| format!("invalid schema: {e}"))
+}
+
+fn procedure<'a>(manifest: &'a Manifest, name: &str) -> Result<&'a Procedure, String> {
+    manifest
+        .procedures
+        .get(name)
+        .ok_or(format!("unknown procedure `{name}`"))
+}
+
+fn param_type<'a>(
+    manifest: &'a Manifest,
+    procedure_name: &str,
+    name: &str,
+) -> Result<&'a TsType, String> {
+    procedure(manifest, procedure_name)?
+        .params
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| &p.ty)
+        .ok_or(format!("`{procedure_name}` has no parameter `{name}`"))
+}
+
+/// Command line values are json, except for strings which may also be given unquoted.
+fn parse_value(name: &str, value: &str, ty: &TsType) -> Result<Value, String> {
+    let is_string = matches!(ty, TsType::Keyword(k) if k == "string");
+    match serde_json::from_str::<Value>(value) {
+        Ok(value) if !is_string || value.is_string() => Ok(value),
+        _ if is_string => Ok(Value::String(value.to_string())),
+        Err(e) => Err(format!("invalid json for `{name}`: {e}")),
+        Ok(_) => unreachable!(),
+    }
+}
+
+/// Orders named arguments like the parameters of `procedure_name`, leaving out the ones read
+/// from headers, cookies or the query string. Missing arguments are `null`, as long as their
+/// type allows it.
+fn request(
+    manifest: &Manifest,
+    procedure_name: &str,
+    mut args: Vec<(String, Value)>,
+) -> Result<Request, String> {
+    let procedure = procedure(manifest, procedure_name)?;
+    let mut request = Request::default();
+    let mut values = Vec::new();
+    for param in procedure.params.iter() {
+        let value = match args.iter().position(|(name, _)| *name == param.name) {
+            Some(i) => args.remove(i).1,
+            None if param.ty.is_nullable() => Value::Null,
+            None => {
+                return Err(format!(
+                    "missing argument `{}: {}` for `{procedure_name}`",
+                    param.name, param.ty
+                ))
+            }
+        };
+        let Some(source) = &param.source else {
+            values.push(value);
+            continue;
+        };
+        // `null` is sent by leaving the value out
+        let text = match value {
+            Value::Null => continue,
+            Value::String(text) => text,
+            value => value.to_string(),
+        };
+        match source {
+            ParamSource::Header(name) => request.headers.push((name.clone(), text)),
+            ParamSource::Cookie(name) => request.cookies.push((name.clone(), text)),
+            ParamSource::Query(name) => request.query.push((name.clone(), text)),
+        }
+    }
+    if let Some((name, _)) = args.first() {
+        return Err(format!("`{procedure_name}` has no parameter `{name}`"));
+    }
+    request.body = Value::Array(values);
+    Ok(request)
+}
+
+/// Encodes a cookie value like `encodeURIComponent` does, the server decodes it.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (b as char).to_string(),
+            b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Sends the request, returns whether it succeeded along with the response body.
+/// An `Err` result counts as a failure.
+fn call(
+    server: &Server,
+    manifest: &Manifest,
+    procedure_name: &str,
+    request: &Request,
+) -> Result<(bool, Value), String> {
+    // dotted names like `users.get` are served at `/users/get`
+    let url = format!("{}/{}", server.url, procedure_name.replace('.', "/"));
+    let mut call = ureq::post(&url).set("content-type", "application/json");
+    for (name, value) in server.headers.iter().chain(request.headers.iter()) {
+        call = call.set(name, value);
+    }
+    if !request.cookies.is_empty() {
+        let cookies: Vec<String> = request
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={}", percent_encode(value)))
+            .collect();
+        call = call.set("cookie", &cookies.join("; "));
+    }
+    for (name, value) in request.query.iter() {
+        call = call.query(name, value);
+    }
+    let (status_ok, response) = match call.send_string(&request.body.to_string()) {
+        Ok(response) => (true, response),
+        Err(ureq::Error::Status(_, response)) => (false, response),
+        Err(e) => return Err(format!("request to `{url}` failed: {e}")),
+    };
+    let status = response.status();
+    let text = response
+        .into_string()
+        .map_err(|e| format!("failed to read the response: {e}"))?;
+    if !status_ok {
+        return Err(format!("`{url}` responded with {status}: {text}"));
+    }
+
+    let value = serde_json::from_str(&text).unwrap_or(Value::String(text));
+    let is_err = procedure(manifest, procedure_name)?.error.is_some()
+        && (value.get("Err").is_some() || value.get("result") == Some(&Value::from("Err")));
+    Ok((!is_err, value))
+}
+
+/// Calls every procedure read from stdin, one json object per line:
+/// `{"procedure": "login", "args": {"email": ".."}}`, `args` may also be
+/// positional, the request body as it is sent.
+/// Prints one json object per line with the response or the error.
+fn batch(server: &Server, manifest: &Manifest) -> bool {
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Call {
+        procedure: String,
+        #[serde(default)]
+        args: Option<Value>,
+    }
+
+    let mut all_ok = true;
+    for (i, line) in std::io::stdin().lock().lines().enumerate() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("error: failed to read stdin: {e}");
+                return false;
+            }
+        };
+        let result = serde_json::from_str::<Call>(&line)
+            .map_err(|e| format!("line {}: {e}", i + 1))
+            .and_then(|c| {
+                let request = match c.args {
+                    Some(Value::Array(args)) => Request {
+                        body: Value::Array(args),
+                        ..Request::default()
+                    },
+                    Some(Value::Object(args)) => {
+                        request(manifest, &c.procedure, args.into_iter().collect())?
+                    }
+                    None => request(manifest, &c.procedure, Vec::new())?,
+                    Some(_) => return Err("`args` must be an array or an object".to_string()),
+                };
+                Ok((
+                    c.procedure.clone(),
+                    call(server, manifest, &c.procedure, &request)?,
+                ))
+            });
+
+        let output: BTreeMap<&str, Value> = match result {
+            Ok((procedure, (ok, response))) => {
+                all_ok &= ok;
+                [
+                    ("procedure", Value::String(procedure)),
+                    ("ok", Value::Bool(ok)),
+                    ("response", response),
+                ]
+                .into()
+            }
+            Err(e) => {
+                all_ok = false;
+                [("ok", Value::Bool(false)), ("error", Value::String(e))].into()
+            }
+        };
+        println!("{}", serde_json::to_string(&output).unwrap());
+    }
+    all_ok
+}
+
+#[test]
+fn test_request() {
+    let manifest = Manifest::from_json(
+        r#"{
+            "version": 1,
+            "procedures": {
+                "login": {
+                    "params": [
+                        { "name": "email", "ty": { "keyword": "string" } },
+                        { "name": "remember", "ty": { "union": [{ "keyword": "boolean" }, { "keyword": "null" }] } },
+                        { "name": "code", "ty": { "keyword": "number" } },
+                        { "name": "tenant", "ty": { "keyword": "string" }, "source": { "header": "x-tenant" } },
+                        { "name": "session", "ty": { "union": [{ "keyword": "string" }, { "keyword": "null" }] }, "source": { "cookie": "session" } },
+                        { "name": "page", "ty": { "keyword": "number" }, "source": { "query": "page" } }
+                    ],
+                    "response": { "keyword": "boolean" }
+                }
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let string = TsType::Keyword("string".to_string());
+    assert_eq!(parse_value("email", "me", &string).unwrap(), "me");
+    assert_eq!(parse_value("email", "\"me\"", &string).unwrap(), "me");
+    assert_eq!(parse_value("email", "1", &string).unwrap(), "1");
+    let number = TsType::Keyword("number".to_string());
+    assert_eq!(parse_value("code", "1", &number).unwrap(), 1);
+    assert!(parse_value("code", "one", &number).is_err());
+
+    let args = vec![
+        ("code".to_string(), Value::from(1)),
+        ("email".to_string(), Value::from("me")),
+        ("tenant".to_string(), Value::from("acme")),
+        ("page".to_string(), Value::from(2)),
+    ];
+    assert_eq!(
+        request(&manifest, "login", args).unwrap(),
+        Request {
+            body: serde_json::json!(["me", null, 1]),
+            headers: vec![("x-tenant".to_string(), "acme".to_string())],
+            cookies: Vec::new(),
+            query: vec![("page".to_string(), "2".to_string())],
+        }
+    );
+    assert!(request(&manifest, "login", Vec::new())
+        .unwrap_err()
+        .contains("missing argument `email: string`"));
+    assert!(request(&manifest, "login", vec![("x".to_string(), Value::Null)]).is_err());
+    assert!(request(&manifest, "logout", Vec::new()).is_err());
+    assert_eq!(percent_encode("a b;ü"), "a%20b%3B%C3%BC");
+}
@@ -0,0 +1,56 @@
+//! `cargo ts-rpc diff`: classifies the differences between two api manifests and fails if any
+//! of them is breaking, so CI can block merging a change that breaks deployed clients.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use ts_rpc_core::manifest::Manifest;
+
+pub fn run(args: impl Iterator<Item = String>) -> ExitCode {
+    let mut paths = Vec::new();
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("error: unexpected argument `{arg}`\n{}", crate::USAGE);
+                return ExitCode::from(2);
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [old, new] = paths.as_slice() else {
+        eprintln!("error: expected two manifests\n{}", crate::USAGE);
+        return ExitCode::from(2);
+    };
+
+    let (old, new) = match (load(old.as_ref()), load(new.as_ref())) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let changes = old.compare(&new);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+    } else if changes.is_empty() {
+        println!("no changes");
+    } else {
+        for change in changes.iter() {
+            println!("{change}");
+        }
+    }
+
+    if changes.iter().any(|c| c.breaking) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn load(path: &Path) -> Result<Manifest, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Manifest::from_json(&content).map_err(|e| format!("{}: {e}", path.display()))
+}
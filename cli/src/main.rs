@@ -0,0 +1,188 @@
+//! `cargo ts-rpc`: exports the typescript clients configured in `ts-rpc.toml` by running the
+//! binary target that calls `ts_rpc::export_main!()`, compares api manifests and calls procedures.
+//!
+//! ```text
+//! cargo ts-rpc [export] [--config <path>] [--check | --watch]
+//! cargo ts-rpc diff <old manifest> <new manifest> [--json]
+//! cargo ts-rpc call <procedure> [--arg <name>=<value | @file>]... [--header <name: value>]...
+//!     [--server <name | url>] [--config <path>] [--schema <manifest>]
+//! cargo ts-rpc call --batch [--header <name: value>]... [--server <name | url>] ...
+//! ```
+
+mod call;
+mod diff;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::time::{Duration, SystemTime};
+
+use ts_rpc_core::export::{ExportConfig, DEFAULT_CONFIG};
+
+const USAGE: &str = "usage: cargo ts-rpc [export] [--config <path>] [--check | --watch]
+       cargo ts-rpc diff <old manifest> <new manifest> [--json]
+       cargo ts-rpc call <procedure> [--arg <name>=<value | @file>]... [--header <name: value>]...
+           [--server <name | url>] [--config <path>] [--schema <manifest>]
+       cargo ts-rpc call --batch [options]";
+
+struct Args {
+    config: PathBuf,
+    check: bool,
+    watch: bool,
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    // Invoked as `cargo ts-rpc`, cargo passes the subcommand name as well.
+    if args.peek().map(String::as_str) == Some("ts-rpc") {
+        args.next();
+    }
+    match args.peek().map(String::as_str) {
+        Some("diff") => {
+            args.next();
+            diff::run(args)
+        }
+        Some("call") => {
+            args.next();
+            call::run(args)
+        }
+        Some("export") => {
+            args.next();
+            run_export(args)
+        }
+        _ => run_export(args),
+    }
+}
+
+fn run_export(args: impl Iterator<Item = String>) -> ExitCode {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let config = match ExportConfig::load(&args.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}: {e}", args.config.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.watch {
+        watch(&args, &config)
+    } else if export(&args, &config) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn parse_args(mut iter: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = Args {
+        config: PathBuf::from(DEFAULT_CONFIG),
+        check: false,
+        watch: false,
+    };
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--check" => args.check = true,
+            "--watch" => args.watch = true,
+            "--config" => {
+                args.config = PathBuf::from(iter.next().ok_or("`--config` requires a path")?)
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    if args.check && args.watch {
+        return Err("`--check` and `--watch` can't be combined".to_string());
+    }
+    Ok(args)
+}
+
+/// Builds and runs the export binary, returns whether it succeeded.
+fn export(args: &Args, config: &ExportConfig) -> bool {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command.args(["run", "--quiet"]);
+    if let Some(package) = &config.package {
+        command.args(["--package", package]);
+    }
+    command.args(["--bin", &config.bin, "--", "--config"]);
+    command.arg(&args.config);
+    if args.check {
+        command.arg("--check");
+    }
+
+    match command.status() {
+        Ok(status) => status.success(),
+        Err(e) => {
+            eprintln!("error: failed to run cargo: {e}");
+            false
+        }
+    }
+}
+
+/// Exports again whenever a file in one of the watched directories changes.
+fn watch(args: &Args, config: &ExportConfig) -> ExitCode {
+    // The outputs may live inside a watched directory, writing them must not trigger an export.
+    let ignored: Vec<PathBuf> = config
+        .outputs
+        .iter()
+        .map(|o| o.path.canonicalize().unwrap_or_else(|_| o.path.clone()))
+        .collect();
+
+    let mut last = None;
+    loop {
+        let current = snapshot(&config.watch, &ignored);
+        if last.as_ref() != Some(&current) {
+            if last.is_some() {
+                println!("change detected, exporting");
+            }
+            export(args, config);
+            // Take the snapshot after exporting, so outputs that couldn't be canonicalized
+            // before they existed don't cause a loop.
+            last = Some(snapshot(&config.watch, &ignored));
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Modification times of all files in `dirs`.
+fn snapshot(dirs: &[PathBuf], ignored: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    fn visit(path: &Path, ignored: &[PathBuf], files: &mut BTreeMap<PathBuf, SystemTime>) {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if metadata.is_dir() {
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                if name == "target" || name.to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                visit(&entry.path(), ignored, files);
+            }
+        } else if !ignored
+            .iter()
+            .any(|i| path.canonicalize().ok().as_ref() == Some(i))
+        {
+            if let Ok(modified) = metadata.modified() {
+                files.insert(path.to_path_buf(), modified);
+            }
+        }
+    }
+
+    let mut files = BTreeMap::new();
+    for dir in dirs {
+        visit(dir, ignored, &mut files);
+    }
+    files
+}
@@ -0,0 +1,144 @@
+//! Request contexts and guards, see [`Api::context`] and `#[ts_export(guard = ..)]`.
+//!
+//! [`Api::context`]: crate::Api::context
+
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::http::{request::Parts, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// The context of the request, resolved by the resolver registered with [`Api::context`].
+/// Like `Axum<External>` it must be the last parameter of the function.
+///
+/// ```ignore
+/// #[ts_export]
+/// async fn me(ctx: Ctx<User>) -> String {
+///     ctx.0.name
+/// }
+/// ```
+///
+/// [`Api::context`]: crate::Api::context
+#[derive(Clone, Debug)]
+pub struct Ctx<C>(pub C);
+
+/// Rejects a request, either in the context resolver or in a guard.
+/// Responds with `401 Unauthorized` or `403 Forbidden` and `{ "kind", "message" }` as json,
+/// which the typescript client throws as `UnauthorizedError` or `ForbiddenError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardError {
+    Unauthorized(String),
+    Forbidden(String),
+}
+
+impl GuardError {
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::Forbidden(message.into())
+    }
+}
+
+impl std::fmt::Display for GuardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuardError::Unauthorized(message) => write!(f, "unauthorized: {message}"),
+            GuardError::Forbidden(message) => write!(f, "forbidden: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GuardError {}
+
+impl IntoResponse for GuardError {
+    fn into_response(self) -> Response {
+        let (status, kind, message) = match self {
+            GuardError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, "unauthorized", message),
+            GuardError::Forbidden(message) => (StatusCode::FORBIDDEN, "forbidden", message),
+        };
+        (status, Json(Rejection { kind, message })).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct Rejection {
+    kind: &'static str,
+    message: String,
+}
+
+/// A guard of a function, added by `#[ts_export(guard = ..)]`.
+#[derive(Debug, Clone, Copy)]
+pub struct Guard {
+    pub name: &'static str,
+    // `None` if the context isn't of the type the guard expects
+    pub check: fn(&(dyn Any + Send + Sync)) -> Option<Result<(), GuardError>>,
+}
+
+type ResolveFuture =
+    Pin<Box<dyn Future<Output = Result<Arc<dyn Any + Send + Sync>, GuardError>> + Send>>;
+
+/// The resolver registered with `Api::context`, added to the extensions of every request.
+#[derive(Clone)]
+pub(crate) struct Resolver(pub(crate) Arc<dyn Fn(HeaderMap) -> ResolveFuture + Send + Sync>);
+
+impl Resolver {
+    pub(crate) fn new<C, Fut>(resolver: impl Fn(HeaderMap) -> Fut + Send + Sync + 'static) -> Self
+    where
+        C: Send + Sync + 'static,
+        Fut: Future<Output = Result<C, GuardError>> + Send + 'static,
+    {
+        Self(Arc::new(move |headers| {
+            let future = resolver(headers);
+            Box::pin(async move { Ok(Arc::new(future.await?) as Arc<dyn Any + Send + Sync>) })
+        }))
+    }
+}
+
+// the resolved context, cached so it is resolved once per request
+#[derive(Clone)]
+struct Resolved(Arc<dyn Any + Send + Sync>);
+
+/// Resolves the context of the request, or returns the one resolved before.
+pub(crate) async fn resolve(parts: &mut Parts) -> Result<Arc<dyn Any + Send + Sync>, Response> {
+    if let Some(Resolved(context)) = parts.extensions.get::<Resolved>() {
+        return Ok(context.clone());
+    }
+    let Some(Resolver(resolver)) = parts.extensions.get::<Resolver>().cloned() else {
+        return Err(internal_error("no context resolver registered, see `Api::context`"));
+    };
+    let context = resolver(parts.headers.clone())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    parts.extensions.insert(Resolved(context.clone()));
+    Ok(context)
+}
+
+/// Runs the guards of a function, before its arguments are deserialized.
+pub(crate) async fn check(guards: &[Guard], parts: &mut Parts) -> Result<(), Response> {
+    if guards.is_empty() {
+        return Ok(());
+    }
+    let context = resolve(parts).await?;
+    for guard in guards {
+        match (guard.check)(&*context) {
+            Some(result) => result.map_err(IntoResponse::into_response)?,
+            None => {
+                return Err(internal_error(&format!(
+                    "the context isn't of the type expected by the guard `{}`",
+                    guard.name
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn internal_error(message: &str) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, message.to_string()).into_response()
+}
@@ -0,0 +1,260 @@
+//! Line based unified diffs, used to show why a generated client is out of date.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    // old line index, new line index
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+const CONTEXT: usize = 3;
+
+/// Renders the difference between `old` and `new` as a unified diff with 3 lines of context.
+/// Returns an empty string only if they are equal, not if just their line endings differ.
+pub(crate) fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = myers(&a, &b);
+    if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+        // `lines` drops `\r` and the last newline
+        out += "the line endings or the newline at the end of the file differ\n";
+        return out;
+    }
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Group changes whose context overlaps into hunks.
+    let mut start = 0;
+    while start < changes.len() {
+        let mut end = start;
+        while end + 1 < changes.len() && changes[end + 1] - changes[end] <= 2 * CONTEXT + 1 {
+            end += 1;
+        }
+        let from = changes[start].saturating_sub(CONTEXT);
+        let to = (changes[end] + CONTEXT + 1).min(ops.len());
+        out += &hunk(&ops[from..to], &a, &b);
+        start = end + 1;
+    }
+    out
+}
+
+fn hunk(ops: &[Op], a: &[&str], b: &[&str]) -> String {
+    // Position of the hunk in both files, taken from its first line that exists there.
+    let old_start = ops
+        .iter()
+        .find_map(|op| match op {
+            Op::Equal(i, _) | Op::Delete(i) => Some(*i),
+            Op::Insert(_) => None,
+        })
+        .unwrap_or(0);
+    let new_start = ops
+        .iter()
+        .find_map(|op| match op {
+            Op::Equal(_, j) | Op::Insert(j) => Some(*j),
+            Op::Delete(_) => None,
+        })
+        .unwrap_or(0);
+    let old_len = ops.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+    let new_len = ops.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+
+    // Empty ranges refer to the line before them.
+    let line = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+    let mut out = format!(
+        "@@ -{},{} +{},{} @@\n",
+        line(old_start, old_len),
+        old_len,
+        line(new_start, new_len),
+        new_len
+    );
+    for op in ops {
+        match op {
+            Op::Equal(i, _) => out += &format!(" {}\n", a[*i]),
+            Op::Delete(i) => out += &format!("-{}\n", a[*i]),
+            Op::Insert(j) => out += &format!("+{}\n", b[*j]),
+        }
+    }
+    out
+}
+
+// Edits searched for between two parts of the files before they are treated as replaced,
+// which bounds the time spent on files that differ everywhere.
+const MAX_EDITS: isize = 1000;
+
+/// Shortest edit script using Myers' algorithm in linear space.
+fn myers(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let mut ops = Vec::new();
+    diff(a, b, (0, a.len()), (0, b.len()), &mut ops);
+    ops
+}
+
+/// Appends the edit script from `a[a_range]` to `b[b_range]`, splitting both at the middle
+/// snake of the script.
+fn diff(
+    a: &[&str],
+    b: &[&str],
+    (mut a_start, mut a_end): (usize, usize),
+    (mut b_start, mut b_end): (usize, usize),
+    ops: &mut Vec<Op>,
+) {
+    while a_start < a_end && b_start < b_end && a[a_start] == b[b_start] {
+        ops.push(Op::Equal(a_start, b_start));
+        a_start += 1;
+        b_start += 1;
+    }
+    let mut suffix = 0;
+    while a_start < a_end && b_start < b_end && a[a_end - 1] == b[b_end - 1] {
+        a_end -= 1;
+        b_end -= 1;
+        suffix += 1;
+    }
+
+    let (old, new) = (&a[a_start..a_end], &b[b_start..b_end]);
+    let snake = match (old.is_empty(), new.is_empty()) {
+        (true, _) | (_, true) => None,
+        _ => middle_snake(old, new),
+    };
+    match snake {
+        Some((x, y, u, v)) => {
+            diff(a, b, (a_start, a_start + x), (b_start, b_start + y), ops);
+            ops.extend((0..u - x).map(|i| Op::Equal(a_start + x + i, b_start + y + i)));
+            diff(a, b, (a_start + u, a_end), (b_start + v, b_end), ops);
+        }
+        None => {
+            ops.extend((a_start..a_end).map(Op::Delete));
+            ops.extend((b_start..b_end).map(Op::Insert));
+        }
+    }
+    ops.extend((0..suffix).map(|i| Op::Equal(a_end + i, b_end + i)));
+}
+
+/// The start and end of the snake in the middle of the shortest edit script, searched from
+/// both ends at once, or `None` if the script is longer than `MAX_EDITS`.
+fn middle_snake(a: &[&str], b: &[&str]) -> Option<(usize, usize, usize, usize)> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let index = |k: isize| (k + offset) as usize;
+    // furthest `x` per diagonal, forward and from the ends of `a` and `b` backwards
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max.min(MAX_EDITS / 2) {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[index(k)] = x;
+            let reverse_k = delta - k;
+            if delta % 2 != 0 && reverse_k.abs() < d && x + backward[index(reverse_k)] >= n {
+                return Some((x0 as usize, y0 as usize, x as usize, (x - k) as usize));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            while x < n && x - k < m && a[(n - x - 1) as usize] == b[(m - x + k - 1) as usize] {
+                x += 1;
+            }
+            backward[index(k)] = x;
+            let forward_k = delta - k;
+            if delta % 2 == 0 && forward_k.abs() <= d && x + forward[index(forward_k)] >= n {
+                return Some((
+                    (n - x) as usize,
+                    (m - x + k) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn test_unified_diff() {
+    assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new"), "");
+    assert_eq!(
+        unified_diff("a\r\nb", "a\nb\n", "old", "new"),
+        "--- old\n+++ new\nthe line endings or the newline at the end of the file differ\n"
+    );
+
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n12\n13\n";
+    assert_eq!(
+        unified_diff(old, new, "old", "new"),
+        "--- old\n+++ new\n\
+         @@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n\
+         @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+    );
+
+    assert_eq!(
+        unified_diff("", "a\n", "old", "new"),
+        "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+a\n"
+    );
+}
+
+#[test]
+fn test_myers() {
+    // the edit script turns `a` into `b`, keeping as many lines as the shortest one does
+    let check = |a: &[&str], b: &[&str], equal: usize| {
+        let ops = myers(a, b);
+        let (mut old, mut new) = (Vec::new(), Vec::new());
+        for op in &ops {
+            match *op {
+                Op::Equal(i, j) => {
+                    assert_eq!(a[i], b[j]);
+                    old.push(a[i]);
+                    new.push(b[j]);
+                }
+                Op::Delete(i) => old.push(a[i]),
+                Op::Insert(j) => new.push(b[j]),
+            }
+        }
+        assert_eq!((old.as_slice(), new.as_slice()), (a, b));
+        assert_eq!(
+            ops.iter().filter(|op| matches!(op, Op::Equal(..))).count(),
+            equal
+        );
+    };
+    check(
+        &["a", "b", "c", "a", "b", "b", "a"],
+        &["c", "b", "a", "b", "a", "c"],
+        4,
+    );
+    check(&["a", "x", "b", "y", "c"], &["x", "a", "b", "c", "y"], 3);
+    check(&["a", "b"], &["c", "d", "e"], 0);
+
+    // files that differ everywhere are replaced
+    let old: Vec<String> = (0..20_000).map(|i| format!("old {i}")).collect();
+    let new: Vec<String> = (0..20_000).map(|i| format!("new {i}")).collect();
+    let old: Vec<&str> = old.iter().map(String::as_str).collect();
+    let new: Vec<&str> = new.iter().map(String::as_str).collect();
+    check(&old, &new, 0);
+    check(&old, &[], 0);
+    check(&[], &new, 0);
+    let mut edited = old.clone();
+    edited[10_000] = "edited";
+    check(&old, &edited, 19_999);
+}
@@ -0,0 +1,100 @@
+//! Files returned by functions, see [`Download`].
+
+use axum::body::Body;
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+
+/// A file returned by a function, sent as the raw body of the response with a
+/// `Content-Disposition: attachment` header instead of being serialized.
+///
+/// The typescript client returns it as a `Blob`, which is a `File` named `filename` if set.
+/// The body can be streamed, e.g. from a file on disk:
+///
+/// ```ignore
+/// #[ts_export]
+/// async fn export_invoices(year: u32) -> Download {
+///     let file = tokio::fs::File::open(format!("invoices/{year}.csv")).await.unwrap();
+///     Download::new("text/csv", Body::from_stream(ReaderStream::new(file)))
+///         .filename(format!("invoices-{year}.csv"))
+/// }
+/// ```
+///
+/// Functions returning `Result<Download, E>` send the `Err` like other results. The client
+/// receives it as a `Result` with the file as its `Ok`.
+#[derive(Debug)]
+pub struct Download {
+    pub filename: Option<String>,
+    pub content_type: String,
+    pub body: Body,
+}
+
+impl Download {
+    pub fn new(content_type: impl Into<String>, body: impl Into<Body>) -> Self {
+        Self {
+            filename: None,
+            content_type: content_type.into(),
+            body: body.into(),
+        }
+    }
+
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+}
+
+impl IntoResponse for Download {
+    fn into_response(self) -> Response {
+        let content_type = HeaderValue::from_str(&self.content_type)
+            .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+        let disposition = content_disposition(self.filename.as_deref());
+        (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CONTENT_DISPOSITION, disposition),
+            ],
+            self.body,
+        )
+            .into_response()
+    }
+}
+
+/// `attachment` with the file name as a quoted ascii fallback and percent encoded utf-8.
+fn content_disposition(filename: Option<&str>) -> HeaderValue {
+    let Some(filename) = filename else {
+        return HeaderValue::from_static("attachment");
+    };
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_content_disposition() {
+    assert_eq!(content_disposition(None), "attachment");
+    assert_eq!(
+        content_disposition(Some("report.pdf")),
+        "attachment; filename=\"report.pdf\"; filename*=UTF-8''report.pdf"
+    );
+    assert_eq!(
+        content_disposition(Some("übersicht \"2024\".csv")),
+        "attachment; filename=\"_bersicht _2024_.csv\"; filename*=UTF-8''%C3%BCbersicht%20%222024%22.csv"
+    );
+}
@@ -0,0 +1,218 @@
+//! Encodings of arguments and responses besides JSON, chosen by `Content-Type` and `Accept`.
+//!
+//! MessagePack (`application/msgpack`) needs the `msgpack` feature and CBOR (`application/cbor`)
+//! the `cbor` feature. Both encode structs as maps, so values have the same shape as in JSON.
+//! Unlike JSON they aren't human readable, which types like `uuid::Uuid` may serialize differently.
+
+use axum::extract::{FromRequest, Request};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::inputs::{self, WithInputs};
+use crate::outcome::{reject, Outcome};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Json,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Encoding {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Encoding::Json),
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Encoding::MsgPack)
+            }
+            #[cfg(feature = "cbor")]
+            "application/cbor" => Some(Encoding::Cbor),
+            _ => None,
+        }
+    }
+
+    fn of_content_type(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::from_media_type)
+    }
+
+    const ALL: &'static [Encoding] = &[
+        Encoding::Json,
+        #[cfg(feature = "msgpack")]
+        Encoding::MsgPack,
+        #[cfg(feature = "cbor")]
+        Encoding::Cbor,
+    ];
+
+    /// How specific a media range matching the encoding is, `None` if it doesn't match.
+    fn specificity(self, essence: &str) -> Option<u8> {
+        match essence {
+            "*/*" => Some(0),
+            "application/*" => Some(1),
+            _ => (Self::from_media_type(essence) == Some(self)).then_some(2),
+        }
+    }
+
+    /// The encoding of the response, the supported one `Accept` gives the highest quality or
+    /// JSON. The most specific media range matching an encoding decides its quality, `q=0`
+    /// rules it out and of equal qualities the one listed first wins.
+    pub(crate) fn accepted(headers: &HeaderMap) -> Self {
+        // lowercase media range without parameters -> quality
+        let ranges: Vec<(String, f32)> = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|range| {
+                let mut parts = range.split(';');
+                let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+                let quality = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (essence, quality)
+            })
+            .collect();
+
+        // quality, position of the media range and the encoding
+        let mut best: Option<(f32, usize, Encoding)> = None;
+        for &encoding in Self::ALL {
+            let matching = ranges
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (essence, q))| Some((encoding.specificity(essence)?, i, *q)))
+                .max_by_key(|(specificity, ..)| *specificity);
+            let Some((_, i, q)) = matching else {
+                continue;
+            };
+            if q > 0.0 && best.map_or(true, |(best_q, best_i, _)| (q, best_i) > (best_q, i)) {
+                best = Some((q, i, encoding));
+            }
+        }
+        best.map_or(Encoding::Json, |(.., encoding)| encoding)
+    }
+
+    /// Encodes the response, failing with `500 Internal Server Error` like `Json`.
+    pub(crate) fn respond<T: Serialize>(self, value: T) -> Response {
+        match self {
+            Encoding::Json => Json(value).into_response(),
+            #[cfg(feature = "msgpack")]
+            Encoding::MsgPack => binary(rmp_serde::to_vec_named(&value), "application/msgpack"),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                let result = ciborium::into_writer(&value, &mut bytes).map(|()| bytes);
+                binary(result, "application/cbor")
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn binary<E: std::fmt::Display>(
+    result: Result<Vec<u8>, E>,
+    content_type: &'static str,
+) -> Response {
+    match result {
+        Ok(bytes) => ([(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Decodes the arguments in the encoding of the `Content-Type`, rejecting them like `Json`
+/// if it isn't supported.
+pub(crate) async fn decode<T: DeserializeOwned>(request: Request) -> Result<T, Response> {
+    #[cfg(feature = "multipart")]
+    if crate::upload::is_multipart(&request) {
+        return crate::upload::decode(request).await;
+    }
+    #[cfg(feature = "tracing")]
+    let request = crate::instrument::record_arguments(request).await?;
+    let values = inputs::values(&request);
+    match Encoding::of_content_type(request.headers()) {
+        Some(Encoding::Json) | None if values.is_empty() => Json::from_request(request, &())
+            .await
+            .map(|Json(value)| value)
+            .map_err(|e| reject(e, Outcome::InvalidArguments)),
+        // the values of headers, cookies and query parameters are inserted while deserializing
+        Some(Encoding::Json) | None => {
+            let Json(value) = Json::<serde_json::Value>::from_request(request, &())
+                .await
+                .map_err(|e| reject(e, Outcome::InvalidArguments))?;
+            inputs::with(values, || WithInputs::deserialize(value))
+                .map(|WithInputs(value)| value)
+                .map_err(|e| invalid_body(e.to_string()))
+        }
+        #[cfg(any(feature = "msgpack", feature = "cbor"))]
+        Some(encoding) => {
+            let bytes = axum::body::Bytes::from_request(request, &())
+                .await
+                .map_err(|e| reject(e, Outcome::InvalidArguments))?;
+            let result = inputs::with(values, || match encoding {
+                #[cfg(feature = "msgpack")]
+                Encoding::MsgPack => rmp_serde::from_slice(&bytes).map_err(|e| e.to_string()),
+                #[cfg(feature = "cbor")]
+                Encoding::Cbor => ciborium::from_reader(&bytes[..]).map_err(|e| e.to_string()),
+                Encoding::Json => unreachable!(),
+            });
+            result.map(|WithInputs(value)| value).map_err(invalid_body)
+        }
+    }
+}
+
+fn invalid_body(e: String) -> Response {
+    let rejection = (
+        axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+        format!("Failed to deserialize the body: {e}"),
+    );
+    reject(rejection, Outcome::InvalidArguments)
+}
+
+#[test]
+fn test_accepted() {
+    let accepted = |accept: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, accept.parse().unwrap());
+        Encoding::accepted(&headers)
+    };
+    assert_eq!(Encoding::accepted(&HeaderMap::new()), Encoding::Json);
+    assert_eq!(accepted("text/html"), Encoding::Json);
+    assert_eq!(accepted("application/json;q=0"), Encoding::Json);
+
+    #[cfg(feature = "msgpack")]
+    {
+        assert_eq!(
+            accepted("application/msgpack, application/json"),
+            Encoding::MsgPack
+        );
+        assert_eq!(
+            accepted("application/json;q=0.5, application/msgpack"),
+            Encoding::MsgPack
+        );
+        assert_eq!(accepted("application/msgpack; q=0.5, */*"), Encoding::Json);
+        assert_eq!(
+            accepted("application/msgpack;q=0, application/*"),
+            Encoding::Json
+        );
+        assert_eq!(
+            accepted("application/json;q=0, */*;q=0.1"),
+            Encoding::MsgPack
+        );
+        assert_eq!(
+            accepted("application/json;q=0.8, application/msgpack;q=0.8"),
+            Encoding::Json
+        );
+        assert_eq!(
+            accepted("text/html, application/msgpack;q=0.9"),
+            Encoding::MsgPack
+        );
+    }
+}
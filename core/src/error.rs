@@ -0,0 +1,145 @@
+//! Errors of exporting typescript clients and the issues found by [`Api::validate`].
+//!
+//! [`Api::validate`]: crate::Api::validate
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::ts_type::ParseError;
+
+/// An error exporting or checking a typescript client.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The api can't be exported as is, see [`Api::validate`](crate::Api::validate).
+    Invalid(Vec<ApiIssue>),
+    /// Generated files differ from the files on disk, with a unified diff per file.
+    OutOfDate(Vec<(PathBuf, String)>),
+    /// A generated typescript type couldn't be parsed.
+    Parse(ParseError),
+    /// The `ts-rpc.toml` config is missing something.
+    Config(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Invalid(issues) => {
+                write!(f, "the api is invalid:")?;
+                for issue in issues {
+                    write!(f, "\n  - {issue}")?;
+                }
+                Ok(())
+            }
+            Error::OutOfDate(files) => {
+                for (i, (path, diff)) in files.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(
+                        f,
+                        "`{}` is out of date, regenerate it with `Api::export_ts_client` or `cargo ts-rpc`.\n\n{diff}",
+                        path.display()
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::Config(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+/// A problem with the functions of an api, see [`Api::validate`](crate::Api::validate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiIssue {
+    /// Functions registered or exported under the same name, by their full paths.
+    /// Only the first registered one is served.
+    DuplicateName { name: String, paths: Vec<String> },
+    /// A registered function that doesn't use `#[ts_export]`, so the client can't call it.
+    NotExported { name: String },
+    /// A function using `#[ts_export]` that isn't registered, by its full path.
+    /// Doesn't fail an export.
+    NotRegistered { path: String },
+    /// Different types with the same typescript name used by one function.
+    TypeNameCollision { function: String, type_name: String },
+    /// A typescript type that can't be parsed, so it is left out of the manifest and validated
+    /// as `unknown`. Doesn't fail an export.
+    UnsupportedType {
+        function: String,
+        ty: String,
+        reason: String,
+    },
+    /// A type passed to [`Api::override_type`](crate::Api::override_type) which its `TS` impl
+    /// inlines, like `uuid::Uuid` as `string`, while no parameter or response is of the type,
+    /// an `Option` or a `Vec` of it, so the override isn't applied anywhere.
+    InlinedOverride { rust_type: String, ts_type: String },
+}
+
+impl ApiIssue {
+    /// Whether the issue fails exporting a typescript client.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            ApiIssue::NotRegistered { .. } | ApiIssue::UnsupportedType { .. }
+        )
+    }
+}
+
+impl fmt::Display for ApiIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiIssue::DuplicateName { name, paths } => write!(
+                f,
+                "more than one function is named `{name}`: `{}`, see `Api::nested_modules`",
+                paths.join("`, `")
+            ),
+            ApiIssue::NotExported { name } => write!(
+                f,
+                "`{name}` is registered but doesn't use `#[ts_export]`"
+            ),
+            ApiIssue::NotRegistered { path } => {
+                write!(f, "`{path}` uses `#[ts_export]` but isn't registered")
+            }
+            ApiIssue::TypeNameCollision {
+                function,
+                type_name,
+            } => write!(
+                f,
+                "`{function}` uses different types named `{type_name}`, rename one with `#[ts(rename = ..)]`"
+            ),
+            ApiIssue::UnsupportedType {
+                function,
+                ty,
+                reason,
+            } => write!(f, "`{function}` uses the unsupported type `{ty}`: {reason}"),
+            ApiIssue::InlinedOverride { rust_type, ts_type } => write!(
+                f,
+                "`{rust_type}` is inlined as `{ts_type}` and only overridden where a parameter or response is of the type, an `Option` or a `Vec` of it, which none is. Override a newtype around it instead"
+            ),
+        }
+    }
+}
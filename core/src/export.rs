@@ -0,0 +1,261 @@
+//! Exporting typescript clients from a dedicated binary instead of as a side effect of
+//! starting the server, see [`export_main!`](crate::export_main) and `cargo ts-rpc`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{Api, Error};
+
+pub const DEFAULT_CONFIG: &str = "ts-rpc.toml";
+
+/// The `ts-rpc.toml` config file.
+///
+/// ```toml
+/// # binary target calling `ts_rpc::export_main!()`
+/// bin = "export"
+/// url = "http://localhost:3003"
+/// # optional, see `Api::manifest`
+/// manifest = "api.json"
+///
+/// [[output]]
+/// path = "frontend/src/api.ts"
+///
+/// [[output]]
+/// path = "admin/src/api.ts"
+/// url = "http://localhost:3004"
+/// validation = true
+/// binary_encodings = true
+///
+/// # a client for node, sending `#[cookie]` parameters itself
+/// [[output]]
+/// path = "scripts/api.ts"
+/// cookie_params = true
+///
+/// # servers for `cargo ts-rpc call --server staging`, `${VAR}` is replaced
+/// # with the environment variable `VAR`
+/// [servers.staging]
+/// url = "https://staging.example.com/api"
+/// headers = { authorization = "Bearer ${STAGING_TOKEN}" }
+/// ```
+///
+/// Relative paths are relative to the directory of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportConfig {
+    // package containing `bin`, if it's not the package in the current directory
+    pub package: Option<String>,
+    #[serde(default = "default_bin")]
+    pub bin: String,
+    // server url used by outputs that don't specify their own
+    pub url: Option<String>,
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<OutputConfig>,
+    // where to write the api manifest, compared by `cargo ts-rpc diff`
+    pub manifest: Option<PathBuf>,
+    // directories watched by `cargo ts-rpc --watch`
+    #[serde(default = "default_watch")]
+    pub watch: Vec<PathBuf>,
+    // name -> server called by `cargo ts-rpc call`
+    #[serde(default)]
+    pub servers: BTreeMap<String, ServerConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    pub path: PathBuf,
+    pub url: Option<String>,
+    // see `Api::ts_validation`, unset keeps the setting of the api
+    pub validation: Option<bool>,
+    // see `Api::ts_traceparent`, unset keeps the setting of the api
+    pub traceparent: Option<bool>,
+    // see `Api::ts_binary_encodings`, unset keeps the setting of the api
+    pub binary_encodings: Option<bool>,
+    // see `Api::ts_cookie_params`, unset keeps the setting of the api
+    pub cookie_params: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub url: String,
+    // sent with every request to the server
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_bin() -> String {
+    "export".to_string()
+}
+
+fn default_watch() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+impl ExportConfig {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&content).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid config `{}`: {e}", path.display()),
+            )
+        })?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for output in config.outputs.iter_mut() {
+            output.path = dir.join(&output.path);
+        }
+        config.manifest = config.manifest.map(|m| dir.join(m));
+        config.watch = config.watch.iter().map(|w| dir.join(w)).collect();
+        Ok(config)
+    }
+
+    /// Writes every configured output and the manifest, or only checks them if `check` is `true`.
+    ///
+    /// With `only_registered` set, only the functions registered on `api` are exported,
+    /// otherwise every function using `#[ts_export]`.
+    pub fn export(&self, mut api: Api, only_registered: bool, check: bool) -> Result<(), Error> {
+        if self.outputs.is_empty() {
+            return Err(Error::Config("no `[[output]]` configured".to_string()));
+        }
+
+        // path -> generated content
+        let mut files = Vec::new();
+        let (validation, traceparent, binary_encodings, cookie_params) = (
+            api.ts_validation,
+            api.ts_traceparent,
+            api.ts_binary_encodings,
+            api.ts_cookie_params,
+        );
+        for output in self.outputs.iter() {
+            let url = output.url.as_ref().or(self.url.as_ref()).ok_or_else(|| {
+                Error::Config(format!(
+                    "no `url` configured for `{}`",
+                    output.path.display()
+                ))
+            })?;
+
+            api.ts_validation = output.validation.unwrap_or(validation);
+            api.ts_traceparent = output.traceparent.unwrap_or(traceparent);
+            api.ts_binary_encodings = output.binary_encodings.unwrap_or(binary_encodings);
+            api.ts_cookie_params = output.cookie_params.unwrap_or(cookie_params);
+            let content = api.ts_client_choice(url, only_registered, only_registered)?;
+            files.push((&output.path, content));
+        }
+        if let Some(path) = &self.manifest {
+            files.push((path, api.manifest(only_registered).to_json()));
+        }
+
+        let mut out_of_date = Vec::new();
+        for (path, content) in files {
+            if check {
+                match crate::check_file(path, &content) {
+                    Ok(()) => println!("{} is up to date", path.display()),
+                    Err(Error::OutOfDate(files)) => out_of_date.extend(files),
+                    Err(e) => return Err(e),
+                }
+            } else {
+                crate::write_file(path, &content)?;
+                println!("wrote {}", path.display());
+            }
+        }
+
+        if out_of_date.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::OutOfDate(out_of_date))
+        }
+    }
+}
+
+/// Entry point generated by [`export_main!`](crate::export_main).
+///
+/// Accepts `--config <path>` (defaults to `ts-rpc.toml`) and `--check`.
+#[doc(hidden)]
+pub fn main(api: Api, only_registered: bool) {
+    let mut config_path = PathBuf::from(DEFAULT_CONFIG);
+    let mut check = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--config" => match args.next() {
+                Some(path) => config_path = PathBuf::from(path),
+                None => exit_with("`--config` requires a path", 2),
+            },
+            _ => exit_with(&format!("unexpected argument `{arg}`"), 2),
+        }
+    }
+
+    let result = ExportConfig::load(&config_path)
+        .map_err(Error::from)
+        .and_then(|config| config.export(api, only_registered, check));
+    if let Err(e) = result {
+        exit_with(&e.to_string(), 1);
+    }
+}
+
+fn exit_with(message: &str, code: i32) -> ! {
+    eprintln!("error: {message}");
+    std::process::exit(code)
+}
+
+/// Generates a `main` function exporting the typescript clients configured in `ts-rpc.toml`,
+/// so they can be exported without starting the server, e.g. by `cargo ts-rpc`.
+///
+/// Without arguments every function using `#[ts_export]` is exported. Passing an expression
+/// evaluating to an [`Api`](crate::Api) exports only the functions registered on it.
+///
+/// ```ignore
+/// // src/bin/export.rs
+/// ts_rpc::export_main!(my_server::api());
+/// ```
+///
+/// When exporting without an `Api`, make sure the crates defining the functions are linked
+/// into the binary, for example by referencing one of their items.
+#[macro_export]
+macro_rules! export_main {
+    () => {
+        fn main() {
+            $crate::export::main($crate::Api::new(), false)
+        }
+    };
+    ($api:expr) => {
+        fn main() {
+            $crate::export::main($api, true)
+        }
+    };
+}
+
+#[test]
+fn test_config() {
+    let config: ExportConfig = toml::from_str(
+        r#"
+        url = "http://localhost:3003"
+
+        [[output]]
+        path = "frontend/api.ts"
+        validation = true
+
+        [servers.staging]
+        url = "https://staging.example.com"
+        headers = { authorization = "Bearer ${TOKEN}" }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.bin, "export");
+    assert_eq!(config.watch, vec![PathBuf::from("src")]);
+    assert_eq!(config.outputs[0].path, PathBuf::from("frontend/api.ts"));
+    assert_eq!(config.outputs[0].validation, Some(true));
+    assert_eq!(config.outputs[0].traceparent, None);
+    assert_eq!(
+        config.servers["staging"].headers["authorization"],
+        "Bearer ${TOKEN}"
+    );
+
+    assert!(toml::from_str::<ExportConfig>("urls = \"typo\"").is_err());
+}
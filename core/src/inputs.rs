@@ -0,0 +1,338 @@
+//! Parameters read from headers, cookies and the query string instead of the arguments,
+//! see [`ParamSource`].
+//!
+//! The client leaves them out of the arguments, so they are put back in at their position
+//! while the arguments are deserialized, in whatever encoding they were sent.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use axum::extract::{Query, Request};
+use axum::http::header;
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::{ParamSource, TsFn};
+
+/// The parameters of a function read from the request, by position.
+/// Added to the requests of the function as an extension.
+#[derive(Debug, Clone)]
+pub(crate) struct Inputs(Arc<[(usize, ParamSource)]>);
+
+impl Inputs {
+    pub(crate) fn of(ts_fn: &TsFn) -> Option<Self> {
+        let inputs: Arc<[_]> = ts_fn
+            .request_types
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| Some((i, p.source?)))
+            .collect();
+        (!inputs.is_empty()).then_some(Inputs(inputs))
+    }
+}
+
+// position -> where it is read from and its value if sent
+type Values = Vec<(usize, ParamSource, Option<String>)>;
+
+/// The values of the inputs of the function handling `request`.
+pub(crate) fn values(request: &Request) -> Values {
+    let Some(Inputs(inputs)) = request.extensions().get::<Inputs>() else {
+        return Vec::new();
+    };
+    let query = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .map(|Query(query)| query)
+        .unwrap_or_default();
+    let headers = request.headers();
+    inputs
+        .iter()
+        .map(|&(i, source)| {
+            let value = match source {
+                ParamSource::Header(name) => headers
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string),
+                ParamSource::Cookie(name) => headers
+                    .get_all(header::COOKIE)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(';'))
+                    .filter_map(|cookie| cookie.trim().split_once('='))
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| percent_decode(value.trim_matches('"'))),
+                ParamSource::Query(name) => query.get(name).cloned(),
+            };
+            (i, source, value)
+        })
+        .collect()
+}
+
+// the typescript client percent encodes cookie values
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+thread_local! {
+    // the values of the arguments being deserialized
+    static VALUES: RefCell<Values> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `deserialize`, which deserializes a [`WithInputs`], with the values of the inputs.
+pub(crate) fn with<R>(values: Values, deserialize: impl FnOnce() -> R) -> R {
+    VALUES.with(|v| *v.borrow_mut() = values);
+    let result = deserialize();
+    VALUES.with(|v| v.borrow_mut().clear());
+    result
+}
+
+/// The tuple of arguments, with the values of the inputs inserted at their position.
+pub(crate) struct WithInputs<T>(pub(crate) T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for WithInputs<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = VALUES.with(|v| std::mem::take(&mut *v.borrow_mut()));
+        if values.is_empty() {
+            T::deserialize(deserializer).map(WithInputs)
+        } else {
+            T::deserialize(Interleave {
+                deserializer,
+                values,
+            })
+            .map(WithInputs)
+        }
+    }
+}
+
+struct Interleave<D> {
+    deserializer: D,
+    values: Values,
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Interleave<D> {
+    type Error = D::Error;
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        let sent = len.saturating_sub(self.values.len());
+        self.deserializer.deserialize_tuple(
+            sent,
+            InterleaveVisitor {
+                visitor,
+                values: self.values,
+            },
+        )
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.deserializer.deserialize_any(InterleaveVisitor {
+            visitor,
+            values: self.values,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+struct InterleaveVisitor<V> {
+    visitor: V,
+    values: Values,
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for InterleaveVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.visitor.expecting(f)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.visitor.visit_seq(Interleaved {
+            seq,
+            values: self.values.into_iter().peekable(),
+            position: 0,
+        })
+    }
+}
+
+struct Interleaved<A> {
+    seq: A,
+    values: std::iter::Peekable<std::vec::IntoIter<(usize, ParamSource, Option<String>)>>,
+    position: usize,
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Interleaved<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        let position = self.position;
+        self.position += 1;
+        match self.values.next_if(|(i, _, _)| *i == position) {
+            Some((_, source, value)) => seed
+                .deserialize(InputDeserializer {
+                    source,
+                    value,
+                    error: PhantomData,
+                })
+                .map(Some),
+            None => self.seq.next_element_seed(seed),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.seq.size_hint().map(|n| n + self.values.len())
+    }
+}
+
+/// Deserializes the text of a header, cookie or query parameter, parsing numbers and booleans.
+struct InputDeserializer<E> {
+    source: ParamSource,
+    value: Option<String>,
+    error: PhantomData<E>,
+}
+
+impl<E: de::Error> InputDeserializer<E> {
+    fn value(self) -> Result<String, E> {
+        let source = self.source;
+        self.value
+            .ok_or_else(|| E::custom(format!("missing the {source}")))
+    }
+
+    fn parse<T: std::str::FromStr>(self) -> Result<T, E> {
+        let source = self.source;
+        let value = self.value()?;
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("invalid value `{value}` of the {source}")))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for InputDeserializer<E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_string(self.value()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Some(_) => visitor.visit_some(self),
+            None => visitor.visit_none(),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_enum(de::value::StringDeserializer::new(self.value()?))
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+impl fmt::Display for ParamSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamSource::Header(name) => write!(f, "header `{name}`"),
+            ParamSource::Cookie(name) => write!(f, "cookie `{name}`"),
+            ParamSource::Query(name) => write!(f, "query parameter `{name}`"),
+        }
+    }
+}
+
+#[test]
+fn test_with_inputs() {
+    let values = vec![
+        (0, ParamSource::Header("x-tenant-id"), Some("7".to_string())),
+        (2, ParamSource::Cookie("session"), None),
+    ];
+    let WithInputs(args) = with(values, || {
+        serde_json::from_str::<WithInputs<(u32, String, Option<String>)>>(r#"["a"]"#)
+    })
+    .unwrap();
+    assert_eq!(args, (7, "a".to_string(), None));
+
+    let values = vec![(1, ParamSource::Query("page"), None)];
+    let error = with(values, || {
+        serde_json::from_str::<WithInputs<(String, u32)>>(r#"["a"]"#)
+    })
+    .err()
+    .unwrap();
+    assert!(error
+        .to_string()
+        .contains("missing the query parameter `page`"));
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("a%20b%3B%C3%BC"), "a b;ü");
+    assert_eq!(percent_decode("100%"), "100%");
+    assert_eq!(percent_decode("%zz"), "%zz");
+}
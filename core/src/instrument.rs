@@ -0,0 +1,179 @@
+//! `tracing` spans for procedure calls, see the `tracing` feature.
+//!
+//! Every call runs inside an `rpc` span with the fields
+//! - `procedure` and `otel.name`: the function name
+//! - `request_id`: the `x-request-id` header
+//! - `trace_id` and `parent_id`: taken from the `traceparent` header
+//! - `arg_sizes`: the size of each json argument in bytes
+//! - `args`: the arguments, only recorded if `DEBUG` is enabled. `#[sensitive]` ones are redacted.
+//!   Neither is recorded for `multipart/form-data` requests, which are streamed, nor for
+//!   parameters read from headers, cookies or the query string. They are recorded when the
+//!   function reads the body, within the body limit of its route.
+//! - `result`: `ok`, `err` for an `Err` result, `rejected` if the `Axum<_>` extractor rejected
+//!   the request, `invalid_arguments` or `failed` for other error responses
+//! - `status` and `latency_ms`
+//!
+//! An event is emitted when the call finishes.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequest, Request};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use serde_json::Value;
+use tracing::{field::Empty, Instrument, Level};
+
+use crate::outcome::{reject, Outcome};
+use crate::TsFn;
+
+/// What the middleware needs to know about a procedure.
+#[derive(Debug, Clone)]
+pub(crate) struct Procedure {
+    pub(crate) name: String,
+    // per sent argument, whether its value must not be logged
+    pub(crate) sensitive: Vec<bool>,
+}
+
+impl Procedure {
+    pub(crate) fn new(name: &str, ts_fn: Option<&TsFn>) -> Self {
+        Self {
+            name: name.to_string(),
+            sensitive: ts_fn
+                .map(|f| {
+                    f.request_types
+                        .iter()
+                        .filter(|p| p.source.is_none())
+                        .map(|p| p.sensitive)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The span of a call, added to its request as an extension to record the arguments in.
+#[derive(Debug, Clone)]
+struct Call {
+    span: tracing::Span,
+    procedure: Arc<Procedure>,
+}
+
+pub(crate) async fn instrument(
+    procedure: Arc<Procedure>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let (trace_id, parent_id) = header(request.headers(), "traceparent")
+        .and_then(parse_traceparent)
+        .unzip();
+    let span = tracing::info_span!(
+        "rpc",
+        otel.name = procedure.name.as_str(),
+        procedure = procedure.name.as_str(),
+        request_id = header(request.headers(), "x-request-id"),
+        trace_id,
+        parent_id,
+        arg_sizes = Empty,
+        args = Empty,
+        result = Empty,
+        status = Empty,
+        latency_ms = Empty,
+    );
+
+    request.extensions_mut().insert(Call {
+        span: span.clone(),
+        procedure,
+    });
+    finish(span, start, next.run(request)).await
+}
+
+/// Records the arguments in the span of the call, if it is instrumented. The body is read
+/// like the extractors do, limited by the `DefaultBodyLimit` of the route, and handed on.
+pub(crate) async fn record_arguments(request: Request) -> Result<Request, Response> {
+    let Some(call) = request.extensions().get::<Call>().cloned() else {
+        return Ok(request);
+    };
+    let (parts, body) = request.into_parts();
+    let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), &())
+        .await
+        .map_err(|e| reject(e, Outcome::InvalidArguments))?;
+    if let Ok(Value::Array(args)) = serde_json::from_slice(&bytes) {
+        let sizes: Vec<usize> = args.iter().map(|a| a.to_string().len()).collect();
+        call.span.record("arg_sizes", format!("{sizes:?}"));
+        if tracing::enabled!(Level::DEBUG) {
+            let args: Vec<Value> = args
+                .into_iter()
+                .enumerate()
+                .map(|(i, arg)| match call.procedure.sensitive.get(i) {
+                    Some(true) => Value::from("[redacted]"),
+                    _ => arg,
+                })
+                .collect();
+            call.span.record("args", Value::Array(args).to_string());
+        }
+    }
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+// Runs the call in `span` and records how it ended.
+async fn finish(
+    span: tracing::Span,
+    start: Instant,
+    call: impl std::future::Future<Output = Response>,
+) -> Response {
+    let response = call.instrument(span.clone()).await;
+
+    let status = response.status();
+    let result = Outcome::of(&response).as_str();
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    span.record("result", result);
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency_ms);
+    span.in_scope(|| {
+        if result == "ok" {
+            tracing::info!(
+                result,
+                status = status.as_u16(),
+                latency_ms,
+                "rpc call finished"
+            );
+        } else {
+            tracing::warn!(
+                result,
+                status = status.as_u16(),
+                latency_ms,
+                "rpc call failed"
+            );
+        }
+    });
+
+    response
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Trace id and parent span id of a w3c `traceparent` header, `00-<trace id>-<parent id>-<flags>`.
+fn parse_traceparent(traceparent: &str) -> Option<(&str, &str)> {
+    let mut parts = traceparent.split('-');
+    let (_version, trace_id, parent_id, _flags) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    let is_hex = |s: &str| s.chars().all(|c| c.is_ascii_hexdigit());
+    (trace_id.len() == 32 && parent_id.len() == 16 && is_hex(trace_id) && is_hex(parent_id))
+        .then_some((trace_id, parent_id))
+}
+
+#[test]
+fn test_parse_traceparent() {
+    assert_eq!(
+        parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        Some(("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7"))
+    );
+    assert_eq!(parse_traceparent("00-xyz-00f067aa0ba902b7-01"), None);
+    assert_eq!(parse_traceparent("garbage"), None);
+}
@@ -0,0 +1,77 @@
+//! Routes describing the live api, see [`Api::introspection`] and [`Api::playground`].
+
+use std::sync::Arc;
+
+use axum::http::{header, request::Parts, StatusCode};
+use axum::response::IntoResponse;
+
+use crate::Api;
+
+pub const SCHEMA_PATH: &str = "/__ts_rpc/schema";
+pub const CLIENT_PATH: &str = "/__ts_rpc/client.ts";
+#[cfg(feature = "playground")]
+pub const PLAYGROUND_PATH: &str = "/__ts_rpc/playground";
+
+#[cfg(feature = "playground")]
+const PLAYGROUND: &str = include_str!("./playground.html");
+
+/// Decides whether a request may access the introspection routes.
+pub type Guard = Arc<dyn Fn(&Parts) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Introspection {
+    // server url used in the served typescript client
+    pub(crate) server_url: String,
+    pub(crate) guard: Guard,
+}
+
+/// The introspection routes for the functions registered on `api` at the time of the call.
+pub(crate) fn router(api: &Api, introspection: &Introspection) -> axum::Router {
+    let schema = Ok(api.manifest(true).to_json());
+    let client = api
+        .ts_client_choice(&introspection.server_url, true, false)
+        .map_err(|e| e.to_string());
+
+    axum::Router::new()
+        .route(
+            SCHEMA_PATH,
+            route(schema, "application/json", introspection.guard.clone()),
+        )
+        .route(
+            CLIENT_PATH,
+            route(
+                client,
+                "text/plain; charset=utf-8",
+                introspection.guard.clone(),
+            ),
+        )
+}
+
+/// The playground page for the functions registered on `api` at the time of the call.
+#[cfg(feature = "playground")]
+pub(crate) fn playground_router(api: &Api, guard: &Guard) -> axum::Router {
+    // The manifest is embedded in the page, so it works without access to the schema route.
+    let manifest = api.manifest(true).to_json().replace("</", "<\\/");
+    let page = Ok(PLAYGROUND.replace("{{manifest}}", &manifest));
+
+    axum::Router::new().route(
+        PLAYGROUND_PATH,
+        route(page, "text/html; charset=utf-8", guard.clone()),
+    )
+}
+
+fn route(
+    body: Result<String, String>,
+    content_type: &'static str,
+    guard: Guard,
+) -> axum::routing::MethodRouter {
+    axum::routing::get(move |parts: Parts| async move {
+        if !guard(&parts) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        match body {
+            Ok(body) => ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        }
+    })
+}
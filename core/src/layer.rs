@@ -0,0 +1,33 @@
+//! Tower layers for single procedures, see [`Api::register_axum_with`] and [`Api::group`].
+//!
+//! [`Api::register_axum_with`]: crate::Api::register_axum_with
+//! [`Api::group`]: crate::Api::group
+
+use std::convert::Infallible;
+
+use axum::extract::Request;
+use axum::response::IntoResponse;
+use axum::routing::{MethodRouter, Route};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A tower layer that can wrap the route of a procedure.
+///
+/// Implemented for every layer accepted by [`MethodRouter::layer`] whose errors are handled,
+/// e.g. with `axum::error_handling::HandleErrorLayer`.
+pub trait ProcedureLayer: Send + Sync + 'static {
+    fn layer_route(&self, route: MethodRouter) -> MethodRouter;
+}
+
+impl<L> ProcedureLayer for L
+where
+    L: Layer<Route> + Clone + Send + Sync + 'static,
+    L::Service: Service<Request> + Clone + Send + 'static,
+    <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+    <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+    <L::Service as Service<Request>>::Future: Send + 'static,
+{
+    fn layer_route(&self, route: MethodRouter) -> MethodRouter {
+        route.layer(self.clone())
+    }
+}
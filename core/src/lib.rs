@@ -1,187 +1,1499 @@
 #![cfg_attr(feature = "tagged-result", feature(auto_traits, negative_impls))]
 
+#[cfg(feature = "axum-router")]
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use std::{
-    collections::{BTreeMap, BTreeSet, HashSet},
+    any::TypeId,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     ops::Deref,
     path::Path,
 };
 
 pub use inventory;
 pub use once_cell;
+#[cfg(feature = "metrics")]
+pub use metrics;
+#[cfg(feature = "prometheus")]
+pub use metrics_exporter_prometheus;
 
 #[cfg(feature = "axum-router")]
-pub use axum_handler::{Axum, HandlerAxum};
+pub use axum_handler::{Axum, Extractor, HandlerAxum};
+#[cfg(feature = "axum-router")]
+pub use context::{Ctx, Guard, GuardError};
+#[cfg(feature = "axum-router")]
+pub use download::Download;
+pub use error::{ApiIssue, Error};
+#[cfg(feature = "axum-router")]
+pub use layer::ProcedureLayer;
+#[cfg(feature = "multipart")]
+pub use upload::{Upload, Uploads};
+
+/// Stands in for `Upload` without the `multipart` feature, so an `Upload` parameter fails to
+/// compile with a note to enable it, see [`ParamType`].
+#[cfg(not(feature = "multipart"))]
+#[doc(hidden)]
+pub enum Upload {}
+use type_override::InlinedType;
+pub use type_override::TypeOverride;
+pub use validate::{Constraint, FieldError, TypeConstraints, Validate};
+#[cfg(feature = "axum-router")]
+pub use with_headers::{InvalidHeader, SameSite, SetCookie, WithHeaders};
 use ts_rs::TS;
 
+#[cfg(feature = "axum-router")]
+mod context;
+mod diff;
+#[cfg(feature = "axum-router")]
+mod download;
+#[cfg(feature = "axum-router")]
+mod encoding;
+mod error;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "axum-router")]
+mod inputs;
+#[cfg(feature = "tracing")]
+mod instrument;
+#[cfg(feature = "introspection")]
+pub mod introspection;
+#[cfg(feature = "axum-router")]
+mod layer;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+#[cfg(feature = "metrics")]
+mod measure;
+#[cfg(feature = "axum-router")]
+mod outcome;
+mod schema;
+pub mod ts_type;
+mod type_override;
+#[cfg(feature = "multipart")]
+mod upload;
+pub mod validate;
+#[cfg(feature = "axum-router")]
+mod with_headers;
+
 const TS_REQUEST: &str = include_str!("./ts/request.ts");
+const TS_VALIDATE: &str = include_str!("./ts/validate.ts");
+const TS_ENCODING: &str = include_str!("./ts/encoding.ts");
+const TS_CONVERT: &str = include_str!("./ts/convert.ts");
 
 pub struct Api {
-    pub registered_fn_names: HashSet<&'static str>,
+    // see `registered_fn_names`
+    registered_fn_names: BTreeSet<String>,
+    // name -> registered function using `#[ts_export]`
+    exports: BTreeMap<String, &'static TsFn>,
+    // name -> full paths of the functions registered under it, the first one is served
+    fn_paths: BTreeMap<String, Vec<String>>,
+    /// Whether the typescript client validates arguments and responses at runtime.
+    pub ts_validation: bool,
+    /// Whether the typescript client sends a `traceparent` header with every request.
+    pub ts_traceparent: bool,
+    /// Whether the typescript client includes the `msgpack` and `cbor` encodings.
+    pub ts_binary_encodings: bool,
+    /// Whether `#[cookie]` parameters are parameters of the typescript client, see
+    /// `ts_cookie_params`.
+    pub ts_cookie_params: bool,
+    /// Whether functions are named after their module, see `nested_modules`.
+    pub nested_modules: bool,
+    // types with a declaration -> their typescript type, see `override_type`
+    type_overrides: BTreeMap<ts_rs::Id, TypeOverride>,
+    // type ids of overridden types their `TS` impl inlines and of an `Option` or `Vec` of them
+    // -> their typescript type referencing the declaration of the inlined type
+    inlined_overrides: BTreeMap<TypeId, (String, InlinedType)>,
     #[cfg(feature = "axum-router")]
     pub axum_router: Option<axum::Router>,
+    // function name -> route of the function, the layers depending on its name are added
+    // in `axum_router`, since `nest` changes it
+    #[cfg(feature = "axum-router")]
+    routes: BTreeMap<String, axum::routing::MethodRouter>,
+    // function name -> number of calls
+    #[cfg(feature = "axum-router")]
+    call_counts: BTreeMap<String, Arc<AtomicU64>>,
+    // layers of the enclosing `group` calls, outermost first
+    #[cfg(feature = "axum-router")]
+    group_layers: Vec<Arc<dyn ProcedureLayer>>,
+    #[cfg(feature = "axum-router")]
+    context: Option<context::Resolver>,
+    #[cfg(feature = "multipart")]
+    uploads: Option<Uploads>,
+    #[cfg(feature = "introspection")]
+    introspection: Option<introspection::Introspection>,
+    #[cfg(feature = "playground")]
+    playground: Option<introspection::Guard>,
 }
 
 impl Api {
     pub fn new() -> Self {
         Self {
-            registered_fn_names: HashSet::new(),
+            registered_fn_names: BTreeSet::new(),
+            exports: BTreeMap::new(),
+            fn_paths: BTreeMap::new(),
+            ts_validation: false,
+            ts_traceparent: false,
+            ts_binary_encodings: false,
+            ts_cookie_params: false,
+            nested_modules: false,
+            type_overrides: BTreeMap::new(),
+            inlined_overrides: BTreeMap::new(),
             #[cfg(feature = "axum-router")]
             axum_router: Some(axum::Router::new()),
+            #[cfg(feature = "axum-router")]
+            routes: BTreeMap::new(),
+            #[cfg(feature = "axum-router")]
+            call_counts: BTreeMap::new(),
+            #[cfg(feature = "axum-router")]
+            group_layers: Vec::new(),
+            #[cfg(feature = "axum-router")]
+            context: None,
+            #[cfg(feature = "multipart")]
+            uploads: None,
+            #[cfg(feature = "introspection")]
+            introspection: None,
+            #[cfg(feature = "playground")]
+            playground: None,
         }
     }
 
-    pub fn ts_client(&self, server_url: impl AsRef<str>) -> std::io::Result<String> {
+    /// Generates runtime validators for every exported type. The client then checks arguments
+    /// before sending them and rejects with an `ArgumentValidationError` if they don't match their
+    /// types. Responses are checked as well, a mismatch rejects with a `ResponseValidationError`
+    /// containing the path of the offending value.
+    pub fn ts_validation(mut self, enabled: bool) -> Self {
+        self.ts_validation = enabled;
+        self
+    }
+
+    /// Makes the typescript client send a w3c `traceparent` header, so traces started in the
+    /// browser continue in the `rpc` spans of the `tracing` feature. The header can be provided
+    /// with the exported `setTraceparentProvider`, by default every request starts a new trace.
+    ///
+    /// Cross origin requests need `traceparent` to be an allowed header in the CORS config.
+    pub fn ts_traceparent(mut self, enabled: bool) -> Self {
+        self.ts_traceparent = enabled;
+        self
+    }
+
+    /// Exports the `msgpack` and `cbor` encodings from the typescript client, which send
+    /// arguments and receive responses in a binary format instead of JSON.
+    /// The server needs the feature of the same name.
+    ///
+    /// ```ts
+    /// import { createClient, msgpack } from './api';
+    ///
+    /// const client = createClient({ encoding: msgpack });
+    /// const points = await client.samples(sensorId);
+    /// ```
+    pub fn ts_binary_encodings(mut self, enabled: bool) -> Self {
+        self.ts_binary_encodings = enabled;
+        self
+    }
+
+    /// Keeps `#[cookie]` parameters in the signatures of the typescript client, which sends them
+    /// in a `Cookie` header. Only runtimes other than browsers, e.g. node, can send the header.
+    ///
+    /// By default the client is made for browsers: cookie parameters are left out and their
+    /// functions are called with `credentials: 'include'`, sending the cookies the browser stores
+    /// for the server. Cross origin requests need credentials to be allowed in the CORS config.
+    pub fn ts_cookie_params(mut self, enabled: bool) -> Self {
+        self.ts_cookie_params = enabled;
+        self
+    }
+
+    /// Names functions after the module they are defined in, so `ban` in `crate::users::admin`
+    /// is called as `users.admin.ban(..)` in typescript and served at `/users/admin/ban`.
+    /// Functions with the same name in different modules don't collide then.
+    ///
+    /// Has to be set before registering functions.
+    pub fn nested_modules(mut self, enabled: bool) -> Self {
+        self.nested_modules = enabled;
+        self
+    }
+
+    /// Types `T` as `ty` in the typescript client instead of the type of its `TS` impl,
+    /// for types of other crates whose impls can't be changed.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .override_type::<Money>("`${number}.${number}`")
+    ///     .override_type::<Timestamp>(
+    ///         TypeOverride::new("Date").revive("(value: string) => new Date(value)"),
+    ///     );
+    /// ```
+    ///
+    /// Types the `TS` impl inlines, like `uuid::Uuid` as `string`, are overridden where
+    /// parameters and responses are of the type, an `Option` or a `Vec` of it. They are typed as
+    /// a declaration named after the type then, `Uuid`. Fields of other types keep the inlined
+    /// type, override a newtype around it for them. An override used by no parameter or
+    /// response is reported as [`ApiIssue::InlinedOverride`].
+    ///
+    /// Validators of `ts_validation` keep checking the values as they are sent.
+    pub fn override_type<T: TS + 'static>(mut self, ty: impl Into<TypeOverride>) -> Self {
+        if T::decl().is_none() {
+            let inlined = InlinedType::new::<T>();
+            for (type_id, ts_type) in inlined.uses::<T>() {
+                self.inlined_overrides
+                    .insert(type_id, (ts_type, inlined.clone()));
+            }
+        }
+        self.type_overrides.insert(T::id(), ty.into());
+        self
+    }
+
+    /// Brands `T` in the typescript client, so ids of different types can't be mixed up.
+    /// See [`TypeOverride::branded`].
+    ///
+    /// ```ignore
+    /// #[derive(TS, Serialize, Deserialize)]
+    /// pub struct UserId(String);
+    ///
+    /// let api = Api::new().brand::<UserId>().brand::<OrderId>();
+    /// ```
+    pub fn brand<T: TS + 'static>(self) -> Self {
+        self.override_type::<T>(TypeOverride::branded())
+    }
+
+    /// The functions to export sorted by name, paired with the name they are exported as.
+    /// Unregistered functions are named by `nested_modules`.
+    fn exported_fns(&self, only_registered: bool) -> Vec<(&str, &'static TsFn)> {
+        let mut ts_fns: Vec<_> = self
+            .exports
+            .iter()
+            .map(|(name, ts_fn)| (name.as_str(), *ts_fn))
+            .collect();
+        if !only_registered {
+            ts_fns.extend(
+                inventory::iter::<LazyTsFn>()
+                    .map(|f| f.0.deref())
+                    .filter(|f| !self.exports.values().any(|e| std::ptr::eq(*e, *f)))
+                    .map(|f| (f.exported_name(self.nested_modules), f)),
+            );
+        }
+        ts_fns.sort_by_key(|(name, _)| *name);
+        ts_fns
+    }
+
+    /// `ts_fn` with its parameters and response of overridden inlined types referencing the
+    /// declaration of the type instead, see `override_type`.
+    fn with_inlined_overrides(&self, ts_fn: &'static TsFn) -> Cow<'static, TsFn> {
+        let inlined = |type_id: Option<TypeId>| self.inlined_overrides.get(&type_id?);
+        let uses_inlined = inlined(ts_fn.response_type_id).is_some()
+            || ts_fn.request_types.iter().any(|p| inlined(p.type_id).is_some());
+        if !uses_inlined {
+            return Cow::Borrowed(ts_fn);
+        }
+
+        let mut ts_fn = ts_fn.clone();
+        let mut declarations = Vec::new();
+        let types = ts_fn
+            .request_types
+            .iter_mut()
+            .map(|p| (p.type_id, &mut p.ty))
+            .chain([(ts_fn.response_type_id, &mut ts_fn.response_type)]);
+        for (type_id, ty) in types {
+            if let Some((ts_type, inlined)) = inlined(type_id) {
+                *ty = ts_type.clone();
+                declarations.push((inlined.id, inlined.declaration()));
+            }
+        }
+        ts_fn.type_declarations.extend(declarations);
+        Cow::Owned(ts_fn)
+    }
+
+    /// Checks the registered functions, without panicking or failing on the first issue.
+    /// Exporting a client fails on the same issues, except for the ones that aren't
+    /// [`ApiIssue::is_error`].
+    ///
+    /// ```ignore
+    /// let issues = api.validate();
+    /// if issues.iter().any(ApiIssue::is_error) {
+    ///     return Err(ts_rpc::Error::Invalid(issues));
+    /// }
+    /// ```
+    pub fn validate(&self) -> Vec<ApiIssue> {
+        let mut issues = self.issues(true);
+        // duplicates count as registered, they are reported as `DuplicateName`
+        let registered: BTreeSet<_> = self.fn_paths.values().flatten().collect();
+        issues.extend(
+            inventory::iter::<LazyTsFn>()
+                .map(|f| f.0.path())
+                .filter(|path| !registered.contains(path))
+                .map(|path| ApiIssue::NotRegistered { path }),
+        );
+        issues
+    }
+
+    /// The issues of the functions exported with `only_registered`, see `validate`.
+    fn issues(&self, only_registered: bool) -> Vec<ApiIssue> {
+        let mut issues = Vec::new();
+
+        // name -> full paths
+        let mut duplicates: BTreeMap<&str, Vec<String>> = self
+            .fn_paths
+            .iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(name, paths)| (name.as_str(), paths.clone()))
+            .collect();
+        // Without `nested_modules` unregistered functions of the same name in different
+        // modules collide with each other and with registered ones.
+        let exported = self.exported_fns(only_registered);
+        for pair in exported.windows(2) {
+            let [(name, a), (next_name, b)] = pair else {
+                unreachable!()
+            };
+            // already reported from `fn_paths`
+            let registered_twice = self.fn_paths.get(*name).is_some_and(|p| p.len() > 1);
+            if name == next_name && !registered_twice {
+                let paths = duplicates.entry(name).or_insert_with(|| vec![a.path()]);
+                paths.push(b.path());
+            }
+        }
+        issues.extend(
+            duplicates
+                .into_iter()
+                .map(|(name, paths)| ApiIssue::DuplicateName {
+                    name: name.to_string(),
+                    paths,
+                }),
+        );
+
+        issues.extend(
+            self.registered_fn_names
+                .iter()
+                .filter(|name| !self.exports.contains_key(*name))
+                .map(|name| ApiIssue::NotExported { name: name.clone() }),
+        );
+
+        // Inlined types are only overridden where parameters and responses use them.
+        let used: BTreeSet<TypeId> = exported
+            .iter()
+            .flat_map(|(_, f)| f.request_types.iter().map(|p| p.type_id))
+            .chain(exported.iter().map(|(_, f)| f.response_type_id))
+            .flatten()
+            .collect();
+        let mut unused: BTreeMap<&str, &InlinedType> = self
+            .inlined_overrides
+            .values()
+            .map(|(_, inlined)| (inlined.rust_type, inlined))
+            .collect();
+        for (type_id, (_, inlined)) in self.inlined_overrides.iter() {
+            if used.contains(type_id) {
+                unused.remove(inlined.rust_type);
+            }
+        }
+        issues.extend(
+            unused
+                .into_values()
+                .map(|inlined| ApiIssue::InlinedOverride {
+                    rust_type: inlined.rust_type.to_string(),
+                    ts_type: inlined.ts_type.clone(),
+                }),
+        );
+
+        for (fn_name, ts_fn) in exported {
+            let unsupported = |ty: &str, e: ts_type::ParseError| ApiIssue::UnsupportedType {
+                function: fn_name.to_string(),
+                ty: ty.to_string(),
+                reason: e.0,
+            };
+            for ty in ts_fn
+                .request_types
+                .iter()
+                .map(|p| &p.ty)
+                .chain([&ts_fn.response_type])
+            {
+                if let Err(e) = ts_type::parse_type(ty) {
+                    issues.push(unsupported(ty, e));
+                }
+            }
+
+            // type name -> its declaration
+            let mut declared = BTreeMap::new();
+            let mut collisions = BTreeSet::new();
+            for declaration in ts_fn.type_declarations.values() {
+                match ts_type::parse_declaration(declaration) {
+                    Ok(decl) => {
+                        let first = declared.entry(decl.name.clone()).or_insert(declaration);
+                        if *first != declaration {
+                            collisions.insert(decl.name);
+                        }
+                    }
+                    Err(e) => issues.push(unsupported(declaration, e)),
+                }
+            }
+            issues.extend(
+                collisions
+                    .into_iter()
+                    .map(|type_name| ApiIssue::TypeNameCollision {
+                        function: fn_name.to_string(),
+                        type_name,
+                    }),
+            );
+        }
+        issues
+    }
+
+    pub fn ts_client(&self, server_url: impl AsRef<str>) -> Result<String, Error> {
         self.ts_client_choice(server_url, true, true)
     }
 
     /// Exports a typescript client to the given file path.
     ///
     /// Only registered functions are exported and if a registered function does not use
-    /// `#[ts_export]` this function panics. If you want to modify this behavior use `ts_client_choice`.
+    /// `#[ts_export]` this function fails. If you want to modify this behavior use `ts_client_choice`.
     pub fn export_ts_client(
         &self,
         server_url: impl AsRef<str>,
         file_path: impl AsRef<Path>,
-    ) -> std::io::Result<()> {
+    ) -> Result<(), Error> {
         let content = self.ts_client_choice(server_url, true, true)?;
+        Ok(write_file(file_path.as_ref(), &content)?)
+    }
 
-        // path without file
-        if let Some(parent) = file_path.as_ref().parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        std::fs::write(file_path, content)
+    /// Checks that the typescript client at `file_path` is the one `export_ts_client` would write.
+    /// Meant for CI, so a committed client can't silently get out of date.
+    ///
+    /// Returns `Error::OutOfDate` containing a unified diff if the file differs.
+    pub fn check_ts_client(
+        &self,
+        server_url: impl AsRef<str>,
+        file_path: impl AsRef<Path>,
+    ) -> Result<(), Error> {
+        let expected = self.ts_client(server_url)?;
+        check_file(file_path.as_ref(), &expected)
+    }
+
+    /// A machine readable description of the exported functions, see [`manifest::Manifest`].
+    ///
+    /// If `only_registered` is `false` all functions using `#[ts_export]` are included.
+    #[cfg(feature = "manifest")]
+    pub fn manifest(&self, only_registered: bool) -> manifest::Manifest {
+        manifest::Manifest::new(self.exported_fns(only_registered))
     }
 
     /// Exports a typescript client to the given file path.
     /// If `only_registered` is `false` all functions, wether registered or not,
     /// will be exported.
+    ///
+    /// Fails with `Error::Invalid` on the issues `validate` reports as errors,
+    /// `ApiIssue::NotExported` only if `registered_must_be_exported` is set.
     pub fn ts_client_choice(
         &self,
         server_url: impl AsRef<str>,
         only_registered: bool,
         registered_must_be_exported: bool,
-    ) -> std::io::Result<String> {
+    ) -> Result<String, Error> {
+        let issues: Vec<_> = self
+            .issues(only_registered)
+            .into_iter()
+            .filter(|issue| match issue {
+                ApiIssue::NotExported { .. } => registered_must_be_exported,
+                issue => issue.is_error(),
+            })
+            .collect();
+        if !issues.is_empty() {
+            return Err(Error::Invalid(issues));
+        }
+
         let mut function_definitions = String::new();
+        // namespace of dotted function names like `users.get` -> their definitions
+        let mut namespaces = BTreeMap::<&str, String>::new();
 
-        // For detecting duplicate function names, declaring exports
+        // For declaring exports
         let mut fn_names = BTreeSet::new();
+        // Whether a function converts values of overridden types, see `TypeOverride::revive`
+        let mut uses_converters = false;
 
-        let mut ts_fns: Vec<_> = inventory::iter::<LazyTsFn>().map(|f| f.0.deref()).collect();
-        ts_fns.sort_by_key(|f| f.name);
+        for (fn_name, ts_fn) in self.exported_fns(only_registered) {
+            fn_names.insert(fn_name);
+            let ts_fn: &TsFn = &self.with_inlined_overrides(ts_fn);
 
-        for ts_fn in ts_fns {
-            if only_registered && !self.registered_fn_names.contains(&ts_fn.name) {
-                continue;
-            }
-
-            // Todo: All registered functions are guaranteed to have a unique name, but if `export_only_registered` is false
-            // there may be a duplicate name that is being exported here.
-            if fn_names.contains(ts_fn.name) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::AlreadyExists,
-                    format!("Duplicate function name: {}. Each exported function must have a unique name.", ts_fn.name),
-                ));
-            } else {
-                fn_names.insert(ts_fn.name);
-            }
-
-            let fn_name = &ts_fn.name;
-            let type_declarations = ts_fn
+            // `register_axum_as` may export a function under another name than its own
+            let (namespace, local_name) = match fn_name.rsplit_once('.') {
+                Some((namespace, local_name)) => (Some(namespace), local_name),
+                None => (None, fn_name),
+            };
+            // Declarations are keyed by type ids, which aren't stable between builds.
+            let mut declarations: Vec<_> = ts_fn
                 .type_declarations
-                .values()
-                .map(|t| format!("export {t}"))
+                .iter()
+                .map(|(id, decl)| match self.type_overrides.get(id) {
+                    Some(type_override) => type_override.declaration(decl),
+                    None => decl.clone(),
+                })
+                .collect();
+            declarations.sort();
+            let type_declarations = declarations
+                .iter()
+                .map(|t| export_declaration(t))
                 .collect::<Vec<_>>()
                 .join("\n");
-            let params = ts_fn
+            // Browsers send their own cookies, see `ts_cookie_params`.
+            let is_cookie = |p: &TsParam| matches!(p.source, Some(ParamSource::Cookie(_)));
+            let signature: Vec<_> = ts_fn
                 .request_types
                 .iter()
-                .map(|t| format!("{}: {}", t.0, prefix_type(fn_name, &t.1)))
+                .filter(|p| self.ts_cookie_params || !is_cookie(p))
+                .collect();
+            let params = signature
+                .iter()
+                .map(|p| match p.upload {
+                    true => format!("{}: {}", p.name, p.ty),
+                    false => format!("{}: {}", p.name, prefix_type(local_name, &p.ty)),
+                })
                 .collect::<Vec<_>>()
                 .join(", ");
-            let param_names = &ts_fn
-                .request_types
+            let param_names = &signature
                 .iter()
-                .map(|t| t.0)
+                .map(|p| p.name)
                 .collect::<Vec<_>>()
                 .join(", ");
-            let response_type = prefix_type(fn_name, &ts_fn.response_type);
-            let server_url = server_url.as_ref();
+            // Headers, cookies and query parameters are left out of the arguments.
+            let sent: Vec<_> = ts_fn
+                .request_types
+                .iter()
+                .filter(|p| p.source.is_none())
+                .collect();
+            let sent_names = sent.iter().map(|p| p.name).collect::<Vec<_>>().join(", ");
+            // Files are sent as parts named after their parameter, the other arguments as json
+            // in the `arguments` part, with the part names in place of the files.
+            let uploads: Vec<_> = sent.iter().filter(|p| p.upload).collect();
+            let body = if uploads.is_empty() {
+                format!("body: [{sent_names}],\n            mediaType: 'application/json',")
+            } else {
+                let arguments = sent
+                    .iter()
+                    .map(|p| match p.upload {
+                        true => format!("'{}'", p.name),
+                        false => p.name.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let files: String = uploads
+                    .iter()
+                    .map(|p| format!("\n                {},", p.name))
+                    .collect();
+                format!("formData: {{\n                arguments: JSON.stringify([{arguments}]),{files}\n            }},")
+            };
+            // The request options sending headers, cookies and query parameters
+            let option_of = |source: &ParamSource| match source {
+                ParamSource::Header(_) => "headers",
+                ParamSource::Cookie(_) => "cookies",
+                ParamSource::Query(_) => "query",
+            };
+            let mut inputs = String::new();
+            for option in ["headers", "cookies", "query"] {
+                let values: String = signature
+                    .iter()
+                    .filter_map(|p| Some((p.name, p.source?)))
+                    .filter(|(_, source)| option_of(source) == option)
+                    .map(|(name, source)| format!("\n                '{}': {name},", source.name()))
+                    .collect();
+                if !values.is_empty() {
+                    inputs += &format!("\n            {option}: {{{values}\n            }},");
+                }
+            }
+            if !self.ts_cookie_params && ts_fn.request_types.iter().any(is_cookie) {
+                inputs += "\n            withCredentials: true,";
+            }
+            let response_type = prefix_type(local_name, &ts_fn.response_type);
+
+            let mut validate_arguments = String::new();
+            let mut validate_response = String::new();
+            let mut schema_namespace = String::new();
+            if self.ts_validation {
+                let schemas = schema::Schemas::new(&ts_fn.type_declarations);
+                let prefix = format!("{local_name}.__schema.");
+                let param_schemas = signature
+                    .iter()
+                    .map(|p| format!("['{}', {}]", p.name, schemas.type_expr(&p.ty, &prefix)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                // checked in the promise, so invalid arguments reject it like other errors
+                validate_arguments = format!(
+                    "\n            validateArguments: () => __validate.assertArguments('{fn_name}', [{param_schemas}], [{param_names}]),"
+                );
+                validate_response = format!(
+                    "\n            validate: (body: any) => __validate.assertResponse('{fn_name}', {}, body),",
+                    schemas.type_expr(&ts_fn.response_type, &prefix)
+                );
+                schema_namespace = format!("\n    {}", schemas.namespace());
+            }
+
+            let traceparent = if self.ts_traceparent {
+                "\n            traceparent: true,"
+            } else {
+                ""
+            };
+
+            // Values of overridden types are converted after receiving and before sending them,
+            // the validators check them as they are sent.
+            let converters = |convert: fn(&TypeOverride) -> Option<&String>| {
+                type_override::Converters::new(
+                    ts_fn
+                        .type_declarations
+                        .iter()
+                        .map(|(id, decl)| (decl, self.type_overrides.get(id).and_then(convert))),
+                )
+            };
+            let revivers = converters(|o| o.revive.as_ref());
+            let serializers = converters(|o| o.serialize.as_ref());
+            let mut convert_namespaces = String::new();
+            let revive = revivers.type_expr(&ts_fn.response_type, &format!("{local_name}.__revive."));
+            let revive = match revive {
+                Some(expr) => {
+                    convert_namespaces += &format!("\n    {}", revivers.namespace("__revive"));
+                    format!("\n            revive: {expr},")
+                }
+                None => String::new(),
+            };
+            // the file of a `Result<Download, E>` is its `Ok`, the `Err` is sent as usual
+            let file = match ts_fn.download && ts_fn.result_types().is_some() {
+                true if cfg!(feature = "tagged-result") => {
+                    "\n            file: (file: Blob) => ({ result: 'Ok', value: file }),"
+                }
+                true => "\n            file: (file: Blob) => ({ Ok: file }),",
+                false => "",
+            };
+            let serialize_arguments: String = signature
+                .iter()
+                .filter(|p| !p.upload)
+                .filter_map(|p| {
+                    let prefix = format!("{local_name}.__serialize.");
+                    let expr = serializers.type_expr(&p.ty, &prefix)?;
+                    Some(format!("    {} = {expr}({});\n", p.name, p.name))
+                })
+                .collect();
+            if !serialize_arguments.is_empty() {
+                convert_namespaces += &format!("\n    {}", serializers.namespace("__serialize"));
+            }
+            uses_converters |= !convert_namespaces.is_empty();
+
+            // Constraints of `#[derive(Validate)]` types, e.g. for validating forms
+            let constraints: BTreeMap<_, _> = ts_fn
+                .type_declarations
+                .keys()
+                .filter_map(validate::constraints)
+                .map(|c| (c.name, c.fields))
+                .collect();
+            let constraints_namespace = if constraints.is_empty() {
+                String::new()
+            } else {
+                let types: String = constraints
+                    .iter()
+                    .map(|(name, fields)| {
+                        let fields = fields
+                            .iter()
+                            .map(|(field, constraints)| {
+                                format!(
+                                    "{}: {}",
+                                    ts_type::field_name(field),
+                                    schema::ts_constraints(constraints)
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!("\n        export const {name} = {{ {fields} }};")
+                    })
+                    .collect();
+                format!("\n    export namespace __constraints {{{types}\n    }}")
+            };
+
+            // Declared response headers are typed in a variant also resolving to the headers
+            let with_meta = if ts_fn.response_headers.is_empty() {
+                String::new()
+            } else {
+                let headers = ts_fn
+                    .response_headers
+                    .iter()
+                    .map(|h| format!("'{}'", h.to_ascii_lowercase()))
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                format!(
+                    r#"
+    /**
+     * Like `{local_name}`, resolving to `{{ data, headers }}` with the headers of the response.
+     */
+    export function withMeta({params}): __request.CancelablePromise<__request.WithMeta<{response_type}, {headers}>> {{
+        return __request.withMeta(() => {local_name}({param_names}));
+    }}"#
+                )
+            };
+
+            let js_doc = js_doc(ts_fn, local_name, self.ts_cookie_params);
+            let route_path = route_path(fn_name);
+            // functions in a namespace have to be exported from it
+            let export = if namespace.is_some() { "export " } else { "" };
 
-            function_definitions += &format!(
+            let definition = format!(
                 r#"
-function {fn_name}({params}): __request.CancelablePromise<{response_type}> {{
-    return __request.request(
-        {{ url: '{server_url}' }},
+{js_doc}{export}function {local_name}({params}): __request.CancelablePromise<{response_type}> {{
+{serialize_arguments}    return __request.request(
+        __config,
         {{
             method: 'POST',
-            url: '/{fn_name}',
-            body: [{param_names}],
-            mediaType: 'application/json',
+            url: '{route_path}',
+            {body}{inputs}{validate_arguments}{validate_response}{file}{revive}{traceparent}
         }}
     )
 }}
-namespace {fn_name} {{
-    {type_declarations}
+{export}namespace {local_name} {{
+    {type_declarations}{schema_namespace}{convert_namespaces}{constraints_namespace}{with_meta}
 }}
 "#
             );
+            match namespace {
+                Some(namespace) => *namespaces.entry(namespace).or_default() += &definition,
+                None => function_definitions += &definition,
+            }
+        }
+        function_definitions += &render_namespaces(&namespaces);
+
+        // Dotted names are exported through their outermost namespace.
+        let exports: Vec<_> = fn_names
+            .into_iter()
+            .map(|name| name.split('.').next().unwrap())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let procedures = exports.join(", ");
+        let exports = format!("export {{\n  {}\n}}", exports.join(",\n  "));
+
+        let mut content = format!(
+            "{}\n\nconst __config: __request.ServerConfig = {{ url: '{}' }};\n{}",
+            exports,
+            server_url.as_ref(),
+            function_definitions
+        );
+
+        // After the namespaces, which are initialized in order.
+        content += &format!(
+            r#"
+const __procedures = {{ {procedures} }};
+
+/**
+ * Creates a client calling the same procedures with another config, e.g. another url or encoding.
+ */
+export const createClient = (config: Partial<__request.ServerConfig>): typeof __procedures =>
+    __request.bindConfig(__procedures, {{ ...__config, ...config }});
+export type ServerConfig = __request.ServerConfig;
+export type Encoding = __request.Encoding;
+export type FieldError = __request.FieldError;
+"#
+        );
+
+        content += &format!("\nnamespace __request {{\n{}\n}}", TS_REQUEST);
+
+        content += &[
+            "ApiError",
+            "UnauthorizedError",
+            "ForbiddenError",
+            "InvalidArgumentsError",
+        ]
+            .map(|class| {
+                format!(
+                    "\nexport const {class} = __request.{class};\nexport type {class} = __request.{class};"
+                )
+            })
+            .concat();
+        content.push('\n');
+
+        if self.ts_traceparent {
+            content +=
+                "\nexport const setTraceparentProvider = __request.setTraceparentProvider;\n";
+        }
+
+        if self.ts_binary_encodings {
+            content += &format!(
+                "\n\nnamespace __encoding {{\n{}\n}}\n{}",
+                TS_ENCODING,
+                ["msgpack", "cbor"]
+                    .map(|encoding| format!("export const {encoding} = __encoding.{encoding};\n"))
+                    .concat()
+            );
+        }
+
+        if uses_converters {
+            content += &format!("\n\nnamespace __convert {{\n{}\n}}\n", TS_CONVERT);
+        }
+
+        if self.ts_validation {
+            content += &format!(
+                "\n\nnamespace __validate {{\n{}\n}}\n{}",
+                TS_VALIDATE,
+                [
+                    "ValidationError",
+                    "ArgumentValidationError",
+                    "ResponseValidationError",
+                ]
+                .map(|class| format!(
+                    "export const {class} = __validate.{class};\nexport type {class} = __validate.{class};\n"
+                ))
+                .concat()
+            );
         }
 
-        // Todo: This does not quite work yet, because there may be a registered function with the same
-        // name as a not registered function that is being exported. Possibly capture the full function paths instead of just the name.
-        if registered_must_be_exported {
-            for registered in self.registered_fn_names.iter() {
-                if !fn_names.contains(registered) {
-                    panic!(
-                        "Function `{}` is registered but not exported. \
-                        If you want to allow registered functions to not be exported, use `Api::export_ts_client_choice` with `export_only_registered` set to `false`.",
-                        registered
-                    );
-                }
+        Ok(content)
+    }
+
+    #[cfg(feature = "axum-router")]
+    #[allow(clippy::extra_unused_type_parameters)]
+    pub fn register_axum<Request, Response, External, F>(self, handler: F) -> Self
+    where
+        F: 'static,
+        HandlerAxum<Request, Response, External, F>: ApiFn,
+    {
+        // Functions without `#[ts_export]` are named after the last segment of their type name.
+        let ts_fn = handler_ts_fn::<F>();
+        let fn_name = match ts_fn {
+            Some(ts_fn) => ts_fn.exported_name(self.nested_modules),
+            None => function_name(&handler),
+        };
+        self.register_axum_named(fn_name, ts_fn, handler)
+    }
+
+    /// Registers `handler` as the function described by `ts_fn`, used by `#[service]`.
+    #[doc(hidden)]
+    #[cfg(feature = "axum-router")]
+    pub fn register_procedure<Request, Response, External, F>(
+        self,
+        ts_fn: &'static TsFn,
+        handler: F,
+    ) -> Self
+    where
+        HandlerAxum<Request, Response, External, F>: ApiFn,
+    {
+        let fn_name = ts_fn.exported_name(self.nested_modules);
+        self.register_axum_named(fn_name, Some(ts_fn), handler)
+    }
+
+    /// Registers `handler` under `fn_name` instead of the name of the function.
+    /// If it uses `#[ts_export]` it is exported as `fn_name` as well.
+    #[cfg(feature = "axum-router")]
+    pub fn register_axum_as<Request, Response, External, F>(
+        self,
+        fn_name: &str,
+        handler: F,
+    ) -> Self
+    where
+        F: 'static,
+        HandlerAxum<Request, Response, External, F>: ApiFn,
+    {
+        self.register_axum_named(fn_name, handler_ts_fn::<F>(), handler)
+    }
+
+    /// Registers `handler` as `fn_name`, exported as described by `ts_fn`.
+    #[cfg(feature = "axum-router")]
+    #[allow(clippy::extra_unused_type_parameters)]
+    fn register_axum_named<Request, Response, External, F>(
+        mut self,
+        fn_name: &str,
+        ts_fn: Option<&'static TsFn>,
+        handler: F,
+    ) -> Self
+    where
+        HandlerAxum<Request, Response, External, F>: ApiFn,
+    {
+        let path = ts_fn.map_or_else(|| std::any::type_name::<F>().to_string(), TsFn::path);
+        if !self.insert_name(fn_name, path) {
+            return self;
+        }
+        if let Some(ts_fn) = ts_fn {
+            self.exports.insert(fn_name.to_string(), ts_fn);
+        }
+        let route = ApiFn::into_route(HandlerAxum {
+            f: handler,
+            _marker: std::marker::PhantomData,
+        });
+        self.add_route(fn_name, ts_fn, route);
+        self
+    }
+
+    /// Records registering the function at `path` as `fn_name`, returns `false` if the name
+    /// is taken. The function registered first is kept, the others are reported by `validate`
+    /// as `ApiIssue::DuplicateName`, failing exports, and traced with the `tracing` feature.
+    #[cfg(feature = "axum-router")]
+    fn insert_name(&mut self, fn_name: &str, path: String) -> bool {
+        let paths = self.fn_paths.entry(fn_name.to_string()).or_default();
+        #[cfg(feature = "tracing")]
+        if let Some(served) = paths.first() {
+            warn_not_served(fn_name, served, &path);
+        }
+        paths.push(path);
+        self.registered_fn_names.insert(fn_name.to_string())
+    }
+
+    /// Adds the functions of `other`. Names registered in both keep the function of `self`
+    /// and are reported by `validate`.
+    ///
+    /// Routes of `other` keep its context resolver and its type overrides are added, those of
+    /// `self` win for the same type. Everything else configured on `other`, like
+    /// `introspection` or the other typescript options, is ignored.
+    #[cfg(feature = "axum-router")]
+    pub fn merge(self, other: Api) -> Self {
+        self.combine(None, other)
+    }
+
+    /// Adds the functions of `other` prefixed with `prefix`, so `invoice` becomes
+    /// `billing.invoice` when nested under `billing`. It is served at `/billing/invoice`
+    /// and called as `billing.invoice(..)` in typescript. See `merge`.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .register_axum(login)
+    ///     .nest("billing", billing::api());
+    /// ```
+    #[cfg(feature = "axum-router")]
+    pub fn nest(self, prefix: &str, other: Api) -> Self {
+        self.combine(Some(prefix), other)
+    }
+
+    #[cfg(feature = "axum-router")]
+    fn combine(mut self, prefix: Option<&str>, mut other: Api) -> Self {
+        let rename = |name: &str| match prefix {
+            Some(prefix) => format!("{prefix}.{name}"),
+            None => name.to_string(),
+        };
+        for (name, paths) in std::mem::take(&mut other.fn_paths) {
+            let renamed = rename(&name);
+            let taken = self.registered_fn_names.contains(&renamed);
+            let registered = self.fn_paths.entry(renamed.clone()).or_default();
+            #[cfg(feature = "tracing")]
+            if let Some(served) = registered.first().filter(|_| taken) {
+                for path in &paths {
+                    warn_not_served(&renamed, served, path);
+                }
+            }
+            registered.extend(paths);
+            if taken {
+                continue;
+            }
+            self.registered_fn_names.insert(renamed.clone());
+            if let Some(ts_fn) = other.exports.remove(&name) {
+                self.exports.insert(renamed.clone(), ts_fn);
+            }
+            if let Some(calls) = other.call_counts.remove(&name) {
+                self.call_counts.insert(renamed.clone(), calls);
+            }
+            if let Some(route) = other.routes.remove(&name) {
+                let route = match &other.context {
+                    Some(resolver) => route.layer(axum::Extension(resolver.clone())),
+                    None => route,
+                };
+                #[cfg(feature = "multipart")]
+                let route = match &other.uploads {
+                    Some(uploads) => route.layer(axum::Extension(uploads.clone())),
+                    None => route,
+                };
+                self.routes.insert(renamed, route);
+            }
+        }
+
+        // `self` decides how types overridden by both are typed
+        for (id, ty) in std::mem::take(&mut other.type_overrides) {
+            self.type_overrides.entry(id).or_insert(ty);
+        }
+        for (type_id, ty) in std::mem::take(&mut other.inlined_overrides) {
+            self.inlined_overrides.entry(type_id).or_insert(ty);
+        }
+
+        // other routes, e.g. added with `prometheus`
+        let other_router = other.axum_router.take().unwrap();
+        if other_router.has_routes() {
+            let router = self.axum_router.take().unwrap();
+            self.axum_router.replace(match prefix {
+                Some(prefix) => router.nest(&route_path(prefix), other_router),
+                None => router.merge(other_router),
+            });
+        }
+        self
+    }
+
+    /// Registers the methods of a `#[service]` as functions named `<service>.<method>`.
+    #[cfg(feature = "axum-router")]
+    pub fn register_service(self, service: impl Service) -> Self {
+        service.register(self)
+    }
+
+    /// Registers `handler` like `register_axum`, wrapping only its route in `layer`.
+    ///
+    /// ```ignore
+    /// let api = Api::new().register_axum_with(upload_avatar, DefaultBodyLimit::max(10 << 20));
+    /// ```
+    #[cfg(feature = "axum-router")]
+    pub fn register_axum_with<Request, Response, External, F>(
+        self,
+        handler: F,
+        layer: impl ProcedureLayer,
+    ) -> Self
+    where
+        F: 'static,
+        HandlerAxum<Request, Response, External, F>: ApiFn,
+    {
+        self.group(layer, |api| api.register_axum(handler))
+    }
+
+    /// Wraps the routes of the functions registered in `f` in `layer`.
+    /// Groups can be nested, the layers of outer groups run first.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .register_axum(login)
+    ///     .group(from_fn(require_admin), |api| {
+    ///         api.register_axum(delete_user).register_axum(ban_user)
+    ///     });
+    /// ```
+    #[cfg(feature = "axum-router")]
+    pub fn group(mut self, layer: impl ProcedureLayer, f: impl FnOnce(Self) -> Self) -> Self {
+        self.group_layers.push(Arc::new(layer));
+        let mut api = f(self);
+        api.group_layers.pop();
+        api
+    }
+
+    /// Registers the resolver of the request context, which functions receive as a `Ctx<C>`
+    /// parameter and guards check. It runs at most once per request, and only for functions
+    /// that use the context. Returning an error rejects the request.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .context(|headers: HeaderMap| async move {
+    ///         let token = headers.get("authorization").ok_or(GuardError::unauthorized("no token"))?;
+    ///         sessions::user(token).await.ok_or(GuardError::unauthorized("invalid token"))
+    ///     })
+    ///     .register_axum(me);
+    /// ```
+    #[cfg(feature = "axum-router")]
+    pub fn context<C, Fut>(
+        mut self,
+        resolver: impl Fn(axum::http::HeaderMap) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        C: Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<C, GuardError>> + Send + 'static,
+    {
+        self.context = Some(context::Resolver::new(resolver));
+        self
+    }
+
+    /// Sets the limits of `Upload` parameters and where large files are streamed to.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .uploads(Uploads::new().max_file_size(10 << 20).memory_limit(256 << 10))
+    ///     .register_axum_with(upload_avatar, DefaultBodyLimit::max(10 << 20));
+    /// ```
+    #[cfg(feature = "multipart")]
+    pub fn uploads(mut self, uploads: Uploads) -> Self {
+        self.uploads = Some(uploads);
+        self
+    }
+
+    #[cfg(feature = "axum-router")]
+    pub fn axum_router(&self) -> axum::Router {
+        let router = self
+            .routes
+            .iter()
+            .fold(self.axum_router.clone().unwrap(), |router, (name, route)| {
+                router.route(&route_path(name), self.procedure_route(name, route.clone()))
+            });
+        let router = match &self.context {
+            Some(resolver) => router.layer(axum::Extension(resolver.clone())),
+            None => router,
+        };
+        #[cfg(feature = "multipart")]
+        let router = match &self.uploads {
+            Some(uploads) => router.layer(axum::Extension(uploads.clone())),
+            None => router,
+        };
+        #[cfg(feature = "introspection")]
+        let router = match &self.introspection {
+            Some(introspection) => router.merge(introspection::router(self, introspection)),
+            None => router,
+        };
+        #[cfg(feature = "playground")]
+        let router = match &self.playground {
+            Some(guard) => router.merge(introspection::playground_router(self, guard)),
+            None => router,
+        };
+        router
+    }
+
+    /// Serves a description of the registered functions as json at `GET /__ts_rpc/schema`
+    /// (see [`manifest::Manifest`]) and the typescript client at `GET /__ts_rpc/client.ts`,
+    /// using `server_url` as the url of the client.
+    ///
+    /// Requests for which `guard` returns `false` are rejected with `403 Forbidden`.
+    /// Both are generated when calling `axum_router`, so functions registered later are not included.
+    ///
+    /// ```ignore
+    /// let api = Api::new()
+    ///     .register_axum(login)
+    ///     .introspection("http://localhost:3003", |parts| {
+    ///         parts.headers.get("x-admin-token").is_some_and(|t| t == "secret")
+    ///     });
+    /// ```
+    #[cfg(feature = "introspection")]
+    pub fn introspection(
+        mut self,
+        server_url: impl Into<String>,
+        guard: impl Fn(&axum::http::request::Parts) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.introspection = Some(introspection::Introspection {
+            server_url: server_url.into(),
+            guard: Arc::new(guard),
+        });
+        self
+    }
+
+    /// Serves an html page at `GET /__ts_rpc/playground` for calling the registered functions
+    /// from the browser. It renders a form for every parameter, lets you set headers for
+    /// `Axum<External>` extractors and shows responses along with their types.
+    ///
+    /// The page doesn't load anything from other hosts. Requests for which `guard` returns
+    /// `false` are rejected with `403 Forbidden`, see `introspection`.
+    #[cfg(feature = "playground")]
+    pub fn playground(
+        mut self,
+        guard: impl Fn(&axum::http::request::Parts) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.playground = Some(Arc::new(guard));
+        self
+    }
+
+    /// Serves the metrics of `handle` in the Prometheus text format at `GET path`.
+    /// The handle comes from installing a recorder, see the `metrics` feature.
+    ///
+    /// ```ignore
+    /// let handle = PrometheusBuilder::new().install_recorder()?;
+    /// let api = Api::new().register_axum(login).prometheus("/metrics", handle);
+    /// ```
+    #[cfg(feature = "prometheus")]
+    pub fn prometheus(
+        mut self,
+        path: &str,
+        handle: metrics_exporter_prometheus::PrometheusHandle,
+    ) -> Self {
+        let route = axum::routing::get(move || async move { handle.render() });
+        let router = self.axum_router.take().unwrap();
+        self.axum_router.replace(router.route(path, route));
+        self
+    }
+
+    /// The names of the registered functions, sorted.
+    ///
+    /// This used to be the public `registered_fn_names: HashSet<&'static str>` field, which
+    /// can't hold the names `nest` and `register_axum_as` build at runtime.
+    pub fn registered_fn_names(&self) -> impl Iterator<Item = &str> {
+        self.registered_fn_names.iter().map(String::as_str)
+    }
+
+    /// How often each registered function has been called since the api was created.
+    /// Useful to find out when a deprecated function can be removed.
+    #[cfg(feature = "axum-router")]
+    pub fn call_counts(&self) -> BTreeMap<&str, u64> {
+        self.call_counts
+            .iter()
+            .map(|(name, count)| (name.as_str(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    #[cfg(feature = "axum-router")]
+    pub fn call_count(&self, fn_name: &str) -> Option<u64> {
+        self.call_counts
+            .get(fn_name)
+            .map(|count| count.load(Ordering::Relaxed))
+    }
+
+    /// Adds the route of a registered function, wrapped in its guards and layers.
+    #[cfg(feature = "axum-router")]
+    fn add_route(
+        &mut self,
+        fn_name: &str,
+        ts_fn: Option<&TsFn>,
+        route: axum::routing::MethodRouter,
+    ) {
+        // Guards are innermost, followed by `#[ts_export(layer = ..)]` and the groups from the
+        // inside out.
+        let guards: Arc<[Guard]> = ts_fn.map_or(Arc::new([]), |f| f.guards.clone().into());
+        let route = if guards.is_empty() {
+            route
+        } else {
+            route.layer(axum::middleware::from_fn(
+                move |request: axum::extract::Request, next: axum::middleware::Next| {
+                    let guards = guards.clone();
+                    async move {
+                        let (mut parts, body) = request.into_parts();
+                        if let Err(response) = context::check(&guards, &mut parts).await {
+                            return outcome::reject(response, outcome::Outcome::Rejected);
+                        }
+                        next.run(axum::extract::Request::from_parts(parts, body))
+                            .await
+                    }
+                },
+            ))
+        };
+        let route = match ts_fn.and_then(inputs::Inputs::of) {
+            Some(inputs) => route.layer(axum::Extension(inputs)),
+            None => route,
+        };
+        let route = match ts_fn.and_then(validate::Parameters::of) {
+            Some(parameters) => route.layer(axum::Extension(parameters)),
+            None => route,
+        };
+        let route = match ts_fn.and_then(|f| f.layer) {
+            Some(layer) => layer(route),
+            None => route,
+        };
+        let route = self
+            .group_layers
+            .iter()
+            .rev()
+            .fold(route, |route, layer| layer.layer_route(route));
+
+        self.call_counts.insert(fn_name.to_string(), Default::default());
+        self.routes.insert(fn_name.to_string(), route);
+    }
+
+    /// Counts the calls of a function, adds `Deprecation` and `Sunset` headers if it is
+    /// deprecated and records metrics and traces under its final name.
+    #[cfg(feature = "axum-router")]
+    fn procedure_route(
+        &self,
+        fn_name: &str,
+        route: axum::routing::MethodRouter,
+    ) -> axum::routing::MethodRouter {
+        use axum::http::HeaderValue;
+
+        let ts_fn = self.exports.get(fn_name).copied();
+        let deprecation = ts_fn.and_then(|f| f.deprecated.clone());
+        let calls = self.call_counts[fn_name].clone();
+
+        let route = route.layer(axum::middleware::from_fn(
+            move |request: axum::extract::Request, next: axum::middleware::Next| {
+                let calls = calls.clone();
+                let deprecation = deprecation.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    let mut response = next.run(request).await;
+                    if let Some(deprecation) = deprecation {
+                        let headers = response.headers_mut();
+                        headers.insert("deprecation", HeaderValue::from_static("true"));
+                        if let Some(sunset) = deprecation
+                            .sunset
+                            .and_then(|s| HeaderValue::from_str(&s).ok())
+                        {
+                            headers.insert("sunset", sunset);
+                        }
+                    }
+                    response
+                }
+            },
+        ));
+
+        #[cfg(feature = "metrics")]
+        let route = {
+            let procedure: Arc<str> = fn_name.into();
+            route.layer(axum::middleware::from_fn(
+                move |request: axum::extract::Request, next: axum::middleware::Next| {
+                    measure::measure(procedure.clone(), request, next)
+                },
+            ))
+        };
+
+        #[cfg(feature = "tracing")]
+        let route = {
+            let procedure = Arc::new(instrument::Procedure::new(fn_name, ts_fn));
+            route.layer(axum::middleware::from_fn(
+                move |request: axum::extract::Request, next: axum::middleware::Next| {
+                    instrument::instrument(procedure.clone(), request, next)
+                },
+            ))
+        };
+
+        route
+    }
+}
+
+/// The path a function is served at, dotted names like `users.get` are nested as `/users/get`.
+fn route_path(fn_name: &str) -> String {
+    format!("/{}", fn_name.replace('.', "/"))
+}
+
+/// Nests the function definitions of dotted names like `users.get` in typescript namespaces,
+/// so they are called as `users.get(..)`.
+fn render_namespaces(namespaces: &BTreeMap<&str, String>) -> String {
+    #[derive(Default)]
+    struct Namespace<'a> {
+        definitions: &'a str,
+        children: BTreeMap<&'a str, Namespace<'a>>,
+    }
+
+    fn render(name: &str, namespace: &Namespace, export: &str) -> String {
+        let children: String = namespace
+            .children
+            .iter()
+            .map(|(name, child)| render(name, child, "export "))
+            .collect();
+        format!(
+            "\n{export}namespace {name} {{{}{children}}}\n",
+            namespace.definitions
+        )
+    }
+
+    let mut root = Namespace::default();
+    for (path, definitions) in namespaces {
+        let namespace = path
+            .split('.')
+            .fold(&mut root, |namespace, name| namespace.children.entry(name).or_default());
+        namespace.definitions = definitions;
+    }
+    root.children
+        .iter()
+        .map(|(name, namespace)| render(name, namespace, ""))
+        .collect()
+}
+
+/// Renders the docs of `ts_fn`, exported as `local_name`, as JSDoc, including `@param`,
+/// `@returns` and `@throws` tags. `# Returns` and `# Errors` sections of the rust docs become
+/// the text of the latter two.
+fn js_doc(ts_fn: &TsFn, local_name: &str, cookie_params: bool) -> String {
+    let (description, sections) = split_doc_sections(ts_fn.docs.as_deref().unwrap_or_default());
+    let mut lines: Vec<String> = description.lines().map(str::to_string).collect();
+
+    let mut tags = Vec::new();
+    // cookie parameters are left out of the signature, see `Api::ts_cookie_params`
+    let params = ts_fn
+        .request_types
+        .iter()
+        .filter(|p| cookie_params || !matches!(p.source, Some(ParamSource::Cookie(_))));
+    for param in params {
+        if let Some(docs) = &param.docs {
+            tags.push(format!("@param {} {}", param.name, docs));
+        }
+    }
+    let returns = sections.get("returns");
+    let errors = sections.get("errors");
+    match ts_fn.result_types() {
+        Some((ok, err)) => {
+            let ok = prefix_type(local_name, &ok);
+            let err = prefix_type(local_name, &err);
+            if let Some(returns) = returns {
+                tags.push(format!("@returns {{{ok}}} {returns}"));
             }
+            tags.push(format!(
+                "@throws {{{err}}} Returned as the `Err` variant. {}",
+                errors.map(String::as_str).unwrap_or_default()
+            ));
         }
+        None => {
+            if let Some(returns) = returns {
+                tags.push(format!("@returns {returns}"));
+            }
+            if let Some(errors) = errors {
+                tags.push(format!("@throws {errors}"));
+            }
+        }
+    }
+    let guards = ts_fn.guard_names();
+    if !guards.is_empty() {
+        tags.push(format!(
+            "@throws {{UnauthorizedError | ForbiddenError}} If rejected by the guards {}.",
+            guards.iter().map(|g| format!("`{g}`")).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    if let Some(deprecation) = &ts_fn.deprecated {
+        let mut tag = String::from("@deprecated");
+        if let Some(note) = &deprecation.note {
+            tag += &format!(" {}", note.trim_end());
+            if deprecation.sunset.is_some() && !tag.ends_with('.') {
+                tag.push('.');
+            }
+        }
+        if let Some(sunset) = &deprecation.sunset {
+            tag += &format!(" Will be removed after {sunset}.");
+        }
+        tags.push(tag);
+    }
+    if ts_fn.docs.is_none()
+        && ts_fn.deprecated.is_none()
+        && guards.is_empty()
+        && tags.iter().all(|t| !t.starts_with("@param"))
+    {
+        return String::new();
+    }
 
-        let exports = format!(
-            "export {{\n  {}\n}}",
-            fn_names.into_iter().collect::<Vec<_>>().join(",\n  ")
-        );
-
-        let content = format!(
-            "{}\n{}\nnamespace __request {{\n{}\n}}",
-            exports, function_definitions, TS_REQUEST
-        );
-
-        Ok(content)
+    if !lines.is_empty() && !tags.is_empty() {
+        lines.push(String::new());
     }
+    lines.extend(tags);
+    ts_type::js_doc(&lines.join("\n"), "")
+}
 
-    #[cfg(feature = "axum-router")]
-    #[allow(clippy::extra_unused_type_parameters)]
-    pub fn register_axum<Request, Response, External, F>(mut self, handler: F) -> Self
-    where
-        HandlerAxum<Request, Response, External, F>: ApiFn,
-    {
-        let fn_name = function_name(&handler);
-        if self.registered_fn_names.contains(fn_name) {
-            panic!(
-                "Function name already registered: `{}`. Each function must have a unique name, since they are all exported from one file in typescript.",
-                fn_name
-            );
+/// Splits markdown sections like `# Errors` off of `docs`, keyed by their lowercase heading.
+fn split_doc_sections(docs: &str) -> (String, BTreeMap<String, String>) {
+    let mut description = String::new();
+    let mut sections = BTreeMap::new();
+    let mut current: Option<(String, String)> = None;
+    for line in docs.lines() {
+        if let Some(heading) = line.strip_prefix("# ") {
+            if let Some((heading, text)) = current.take() {
+                sections.insert(heading, text.trim().to_string());
+            }
+            current = Some((heading.trim().to_lowercase(), String::new()));
+        } else if let Some((_, text)) = &mut current {
+            *text += line;
+            *text += "\n";
+        } else {
+            description += line;
+            description += "\n";
         }
-        self.registered_fn_names.insert(fn_name);
-        ApiFn::register(
-            HandlerAxum {
-                f: handler,
-                _marker: std::marker::PhantomData,
-            },
-            &mut self,
-        );
-        self
     }
+    if let Some((heading, text)) = current {
+        sections.insert(heading, text.trim().to_string());
+    }
+    (description.trim().to_string(), sections)
+}
 
-    #[cfg(feature = "axum-router")]
-    pub fn axum_router(&self) -> axum::Router {
-        self.axum_router.clone().unwrap()
+/// Exports a type declaration, adding the docs collected with `#[derive(TsDocs)]`.
+/// Docs already present in the declaration take precedence.
+fn export_declaration(declaration: &str) -> String {
+    let Ok(mut decl) = ts_type::parse_declaration(declaration) else {
+        return format!("export {declaration}");
+    };
+    let Some(type_docs) = inventory::iter::<TypeDocs>().find(|d| d.name == decl.name) else {
+        return format!("export {declaration}");
+    };
+
+    let docs = decl.docs.take().or(type_docs.docs.map(str::to_string));
+    if let ts_type::TsType::Object(fields) = &mut decl.ty {
+        for field in fields.iter_mut() {
+            if field.docs.is_none() {
+                field.docs = type_docs
+                    .fields
+                    .iter()
+                    .find(|(name, _)| *name == field.name)
+                    .map(|(_, docs)| docs.to_string());
+            }
+        }
     }
+    let docs = docs
+        .map(|docs| ts_type::js_doc(&docs, ""))
+        .unwrap_or_default();
+    format!("{docs}export {decl}")
+}
+
+/// Traces that the function at `path` isn't served, since `served` was registered as `fn_name`
+/// first. `Api::validate` reports it as `ApiIssue::DuplicateName`, which fails exports.
+#[cfg(feature = "tracing")]
+fn warn_not_served(fn_name: &str, served: &str, path: &str) {
+    tracing::warn!(
+        "`{path}` isn't served, `{served}` is already registered as `{fn_name}`, see `Api::nested_modules`"
+    );
 }
 
 /// s must start with `open`
@@ -213,24 +1525,45 @@ fn prefix_type(prefix: &str, mut t: &str) -> String {
         if let Some(delimiters) = delimiters {
             let end = find_matching_delimiter(t, delimiters.0, delimiters.1).unwrap();
 
+            // an entry is made of several parts in unions, e.g. `[string | null, number]`
             let mut entries = Vec::new();
+            let mut entry = String::new();
             let mut inner = &t[1..end];
             while !inner.is_empty() {
-                let (entry, next) = prefix_type_inner(prefix, inner);
+                let (part, next) = prefix_type_inner(prefix, inner);
+                entry.push_str(&part);
+                inner = next.trim();
+                if let Some(next) = inner.strip_prefix(',') {
+                    entries.push(std::mem::take(&mut entry));
+                    inner = next.trim();
+                }
+            }
+            if !entry.is_empty() {
                 entries.push(entry);
-                inner = next.trim_start_matches(',').trim();
             }
 
             (
                 format!("{}{}{}", delimiters.0, entries.join(", "), delimiters.1),
                 &t[end + 1..],
             )
+        } else if let Some(quote @ ('"' | '\'')) = t.chars().next() {
+            // a string literal
+            let end = t[1..].find(quote).map_or(t.len(), |end| end + 2);
+            (t[..end].to_string(), &t[end..])
+        } else if t.starts_with(['|', '&']) {
+            // an operator of a union or intersection, e.g. `string | null`
+            (format!(" {} ", &t[..1]), &t[1..])
         } else {
             // we have a type ident
             let end = t
                 .find(|c: char| !c.is_alphanumeric() && c != '_')
                 .unwrap_or(t.len());
             let ty = &t[..end];
+            if ty.is_empty() {
+                // any other symbol is kept as is
+                let len = t.chars().next().map_or(0, char::len_utf8);
+                return (t[..len].to_string(), &t[len..]);
+            }
 
             let ty = if !is_ts_intrinstic_type(ty) {
                 format!("{}.{}", prefix, ty)
@@ -262,14 +1595,43 @@ fn prefix_type(prefix: &str, mut t: &str) -> String {
 fn is_ts_intrinstic_type(t: &str) -> bool {
     matches!(
         t,
-        "string" | "number" | "boolean" | "any" | "void" | "never" | "unknown" | "null" | "Array"
+        "string"
+            | "number"
+            | "boolean"
+            | "any"
+            | "void"
+            | "never"
+            | "unknown"
+            | "null"
+            | "Array"
+            | "Blob"
     )
 }
 
+#[test]
+fn test_download_result() {
+    let mut ts_fn = TsFn::new("invoice");
+    ts_fn.set_download_result_response::<String>();
+    assert!(ts_fn.download);
+    assert_eq!(
+        ts_fn.result_types(),
+        Some(("Blob".to_string(), "string".to_string()))
+    );
+    assert_eq!(
+        prefix_type("invoice", &ts_fn.response_type),
+        "invoice.Result<Blob, string>"
+    );
+}
+
 #[test]
 fn test_prefix_type() {
     assert_eq!(prefix_type("foo", "bar"), "foo.bar");
     assert_eq!(prefix_type("foo", "string"), "string");
+    assert_eq!(prefix_type("foo", "string | null"), "string | null");
+    assert_eq!(
+        prefix_type("foo", "[bar | null, Array<\"a\" | 'b'>]"),
+        "[foo.bar | null, Array<\"a\" | 'b'>]"
+    );
     assert_eq!(prefix_type("foo", "bar<baz>"), "foo.bar<foo.baz>");
     assert_eq!(
         prefix_type("foo", "bar<baz, qux>"),
@@ -289,6 +1651,126 @@ fn test_prefix_type() {
     );
 }
 
+#[test]
+fn test_js_doc() {
+    let mut ts_fn = TsFn::new("login");
+    ts_fn.add_request_type::<String>("email");
+    ts_fn.set_param_docs("email", "The email address.");
+    ts_fn.set_response_type::<Result<String, String>>();
+    ts_fn.set_docs("Logs a user in.\n\n# Errors\nIf the password is wrong.");
+
+    assert_eq!(
+        js_doc(&ts_fn, "login", false),
+        "/**\n * Logs a user in.\n *\n * @param email The email address.\n * @throws {string} Returned as the `Err` variant. If the password is wrong.\n */\n"
+    );
+    assert_eq!(js_doc(&TsFn::new("logout"), "logout", false), "");
+
+    let mut ts_fn = TsFn::new("me");
+    ts_fn.add_request_type::<String>("session");
+    ts_fn.set_param_docs("session", "The session token.");
+    ts_fn.set_param_source("session", ParamSource::Cookie("session"));
+    assert_eq!(js_doc(&ts_fn, "me", false), "");
+    assert_eq!(
+        js_doc(&ts_fn, "me", true),
+        "/** @param session The session token. */\n"
+    );
+
+    let mut ts_fn = TsFn::new("logout");
+    ts_fn.set_deprecated(Some("Use `sign_out` instead."), None);
+    ts_fn.set_sunset("Fri, 01 Jan 2027 00:00:00 GMT");
+    assert_eq!(
+        js_doc(&ts_fn, "logout", false),
+        "/** @deprecated Use `sign_out` instead. Will be removed after Fri, 01 Jan 2027 00:00:00 GMT. */\n"
+    );
+
+    #[cfg(feature = "axum-router")]
+    {
+        let mut ts_fn = TsFn::new("ban_user");
+        ts_fn.add_guard("require_admin", |_| Some(Ok(())));
+        assert_eq!(
+            js_doc(&ts_fn, "ban_user", false),
+            "/** @throws {UnauthorizedError | ForbiddenError} If rejected by the guards `require_admin`. */\n"
+        );
+    }
+}
+
+#[test]
+fn test_nested_name() {
+    let mut ts_fn = TsFn::new("ban");
+    assert_eq!(ts_fn.exported_name(true), "ban");
+    ts_fn.set_module_path("app::users::admin");
+    assert_eq!(ts_fn.exported_name(false), "ban");
+    assert_eq!(ts_fn.exported_name(true), "users.admin.ban");
+    assert_eq!(route_path(ts_fn.exported_name(true)), "/users/admin/ban");
+}
+
+#[cfg(feature = "axum-router")]
+#[test]
+fn test_nest() {
+    let billing = Api::new().register_axum_as("invoice", |id: u32| async move { id });
+    let api = Api::new()
+        .register_axum_as("login", || async {})
+        .nest("billing", billing);
+    assert_eq!(
+        api.registered_fn_names().collect::<Vec<_>>(),
+        ["billing.invoice", "login"]
+    );
+    assert_eq!(
+        api.call_counts().into_keys().collect::<Vec<_>>(),
+        ["billing.invoice", "login"]
+    );
+}
+
+#[test]
+fn test_inlined_override() {
+    let mut ts_fn = TsFn::new("get");
+    ts_fn.add_request_type::<Option<u64>>("id");
+    ts_fn.add_request_type::<String>("name");
+    ts_fn.set_response_type::<Vec<u64>>();
+    let ts_fn: &'static TsFn = Box::leak(Box::new(ts_fn));
+
+    let api = Api::new().override_type::<u64>(TypeOverride::branded());
+    let overridden = api.with_inlined_overrides(ts_fn);
+    assert_eq!(overridden.request_types[0].ty, "u64 | null");
+    assert_eq!(overridden.request_types[1].ty, "string");
+    assert_eq!(overridden.response_type, "Array<u64>");
+    assert_eq!(
+        overridden.type_declarations[&u64::id()],
+        format!("type u64 = {};", u64::name_with_generics())
+    );
+    assert!(matches!(
+        api.with_inlined_overrides(ts_fn),
+        Cow::Owned(_)
+    ));
+    assert!(matches!(
+        Api::new().with_inlined_overrides(ts_fn),
+        Cow::Borrowed(_)
+    ));
+
+    // not used by any exported function
+    assert!(api
+        .issues(true)
+        .iter()
+        .any(|issue| matches!(issue, ApiIssue::InlinedOverride { .. }) && issue.is_error()));
+}
+
+#[cfg(feature = "axum-router")]
+#[test]
+fn test_merge_collision() {
+    let api = Api::new()
+        .register_axum_as("login", || async {})
+        .merge(Api::new().register_axum_as("login", || async {}));
+    assert!(matches!(
+        &api.validate()[..],
+        [ApiIssue::DuplicateName { name, paths }, ApiIssue::NotExported { .. }, ..]
+            if name == "login" && paths.len() == 2
+    ));
+    assert!(matches!(
+        api.ts_client("http://localhost:3003"),
+        Err(Error::Invalid(issues)) if issues.len() == 2
+    ));
+}
+
 #[test]
 fn test_void() {
     println!(
@@ -303,20 +1785,120 @@ impl Default for Api {
     }
 }
 
+fn write_file(path: &Path, content: &str) -> std::io::Result<()> {
+    // path without file
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)
+}
+
+/// Compares the generated file at `path` with `expected`, see `Api::check_ts_client`.
+fn check_file(path: &Path, expected: &str) -> Result<(), Error> {
+    let actual = match std::fs::read_to_string(path) {
+        Ok(actual) => actual,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let diff = diff::unified_diff(&actual, expected, &path.display().to_string(), "generated");
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::OutOfDate(vec![(path.to_path_buf(), diff)]))
+    }
+}
+
+/// Panics with a diff if the typescript client at `file_path` is out of date, see `Api::check_ts_client`.
+///
+/// Meant to be used in a test. If the environment variable `TS_RPC_UPDATE` is set
+/// the client is written instead.
+pub fn assert_ts_client_up_to_date(
+    api: &Api,
+    server_url: impl AsRef<str>,
+    file_path: impl AsRef<Path>,
+) {
+    if std::env::var_os("TS_RPC_UPDATE").is_some() {
+        api.export_ts_client(server_url, file_path).unwrap();
+        return;
+    }
+    if let Err(e) = api.check_ts_client(server_url, file_path) {
+        panic!("{e}\nSet `TS_RPC_UPDATE=1` to update it.");
+    }
+}
+
+#[cfg(feature = "axum-router")]
 pub trait ApiFn {
-    fn register(self, api: &mut Api);
+    /// The route calling the function.
+    fn into_route(self) -> axum::routing::MethodRouter;
+}
+
+/// A struct whose methods are registered as functions, implemented by `#[service]`.
+/// The struct is shared between the handlers of its methods.
+#[cfg(feature = "axum-router")]
+pub trait Service: Send + Sync + Sized + 'static {
+    /// Registers every method, see [`Api::register_service`].
+    fn register(self, api: Api) -> Api;
 }
 
 #[cfg(feature = "axum-router")]
 pub mod axum_handler {
-    use super::{function_name, Api, ApiFn};
-    use axum::extract::{FromRequest, FromRequestParts, Json};
+    use super::ApiFn;
+    use crate::context::Ctx;
+    use crate::download::Download;
+    use crate::encoding::{self, Encoding};
+    use crate::outcome::{reject, Outcome};
+    use crate::validate::{validate_arguments, Parameters};
+    use crate::with_headers::{self, WithHeaders};
+    use std::any::Any;
+    use axum::extract::FromRequestParts;
+    use axum::http::{request::Parts, HeaderMap};
     use axum::response::IntoResponse;
     use serde::de::DeserializeOwned;
+    #[cfg(not(feature = "tagged-result"))]
+    use serde::Serialize;
 
     #[derive(Clone, Debug)]
     pub struct Axum<T>(pub T);
 
+    /// The last parameter of a function, taken from the request instead of the json arguments.
+    /// Implemented for `Axum<External>` and `Ctx<C>`.
+    pub trait Extractor: Sized + Send + 'static {
+        fn extract(
+            parts: &mut Parts,
+        ) -> impl std::future::Future<Output = Result<Self, axum::response::Response>> + Send;
+    }
+
+    impl<External> Extractor for Axum<External>
+    where
+        External: FromRequestParts<()> + Sync + Send + 'static,
+    {
+        async fn extract(parts: &mut Parts) -> Result<Self, axum::response::Response> {
+            External::from_request_parts(parts, &())
+                .await
+                .map(Axum)
+                .map_err(|e| reject(e, Outcome::Rejected))
+        }
+    }
+
+    impl<C> Extractor for Ctx<C>
+    where
+        C: Clone + Sync + Send + 'static,
+    {
+        async fn extract(parts: &mut Parts) -> Result<Self, axum::response::Response> {
+            let context = crate::context::resolve(parts)
+                .await
+                .map_err(|response| reject(response, Outcome::Rejected))?;
+            match context.downcast_ref::<C>() {
+                Some(context) => Ok(Ctx(context.clone())),
+                None => Err(crate::context::internal_error(&format!(
+                    "the context isn't a `{}`, see `Api::context`",
+                    std::any::type_name::<C>()
+                ))),
+            }
+        }
+    }
+
     #[derive(Debug)]
     pub struct HandlerAxum<Request, Response, External, F> {
         pub f: F,
@@ -332,150 +1914,180 @@ pub mod axum_handler {
         }
     }
 
+    /// Turns the return value of a function into the response, serialized in `encoding`
+    /// unless it is a `Download` or the `Ok` of a `Result` is. The headers of the `WithHeaders`
+    /// in it are added to the response.
+    ///
+    /// `M` is `Serialized` or `File`, so results of files don't overlap with serializable
+    /// results, which `HandlerAxum` infers from the only impl the response has.
+    trait Respond<M> {
+        fn respond(self, encoding: Encoding) -> axum::response::Response;
+    }
+
+    pub struct Serialized;
+    pub struct File;
+
     #[cfg(feature = "tagged-result")]
-    trait ResponseBound:
-        Sync + Send + 'static + crate::specialized_serialization::SpecializedSerialize
+    impl<T: crate::specialized_serialization::SpecializedSerialize> Respond<Serialized> for T {
+        fn respond(self, encoding: Encoding) -> axum::response::Response {
+            let is_err = self.is_err();
+            let response = with_headers::collect(|| encoding.respond(self.boxed()));
+            crate::outcome::respond(response, is_err)
+        }
+    }
+    #[cfg(not(feature = "tagged-result"))]
+    impl<T: Serialize> Respond<Serialized> for T {
+        fn respond(self, encoding: Encoding) -> axum::response::Response {
+            // probes the `Serialize` impl, which adds the headers of a `WithHeaders` that
+            // `collect` clears
+            let is_err = crate::outcome::is_err(&self);
+            let response = with_headers::collect(|| encoding.respond(self));
+            crate::outcome::respond(response, is_err)
+        }
+    }
+
+    impl Respond<File> for Download {
+        fn respond(self, _encoding: Encoding) -> axum::response::Response {
+            crate::outcome::respond(self.into_response(), false)
+        }
+    }
+
+    impl Respond<File> for WithHeaders<Download> {
+        fn respond(self, _encoding: Encoding) -> axum::response::Response {
+            let mut response = self.data.into_response();
+            response.headers_mut().extend(self.headers);
+            crate::outcome::respond(response, false)
+        }
+    }
+
+    // the `Err` is serialized like the ones of other results
+    impl<E> Respond<File> for Result<Download, E>
+    where
+        Result<(), E>: Respond<Serialized>,
     {
+        fn respond(self, encoding: Encoding) -> axum::response::Response {
+            match self {
+                Ok(download) => download.respond(encoding),
+                Err(e) => Err::<(), E>(e).respond(encoding),
+            }
+        }
     }
-    #[cfg(feature = "tagged-result")]
-    impl<T> ResponseBound for T where
-        T: Sync + Send + 'static + crate::specialized_serialization::SpecializedSerialize
+
+    impl<E> Respond<File> for Result<WithHeaders<Download>, E>
+    where
+        Result<(), E>: Respond<Serialized>,
     {
+        fn respond(self, encoding: Encoding) -> axum::response::Response {
+            match self {
+                Ok(download) => download.respond(encoding),
+                Err(e) => Err::<(), E>(e).respond(encoding),
+            }
+        }
     }
-    #[cfg(not(feature = "tagged-result"))]
-    trait ResponseBound: Sync + Send + 'static + Serialize {}
-    #[cfg(not(feature = "tagged-result"))]
-    impl<T> ResponseBound for T where T: Sync + Send + 'static + Serialize {}
 
-    impl<Response, External, F, Fut> ApiFn for HandlerAxum<(), Response, Axum<External>, F>
+    trait ResponseBound<M>: Send + 'static + Respond<M> {}
+    impl<T, M> ResponseBound<M> for T where T: Send + 'static + Respond<M> {}
+
+    impl<Response, M, External, F, Fut> ApiFn for HandlerAxum<(), (Response, M), External, F>
     where
-        Response: ResponseBound,
-        External: FromRequestParts<()> + Sync + Send + 'static,
+        Response: ResponseBound<M>,
+        M: Send + Sync + 'static,
+        External: Extractor,
         Fut: std::future::Future<Output = Response> + Send + 'static,
-        F: Sync + Send + 'static + Clone + Fn(Axum<External>) -> Fut,
+        F: Sync + Send + 'static + Clone + Fn(External) -> Fut,
     {
-        fn register(self, api: &mut Api) {
-            let path = format!("/{}", function_name(&self.f));
-
+        fn into_route(self) -> axum::routing::MethodRouter {
             let handler = move |request: axum::http::Request<axum::body::Body>| async {
                 let this = self;
 
-                let (mut parts, body) = request.into_parts();
-                let external: External = FromRequestParts::from_request_parts(&mut parts, &())
-                    .await
-                    .map_err(|e: <External as FromRequestParts<()>>::Rejection| {
-                        e.into_response()
-                    })?;
-
-                let res = (this.f)(Axum(external)).await;
-                #[cfg(feature = "tagged-result")]
-                let res = res.boxed();
+                let encoding = Encoding::accepted(request.headers());
+                let (mut parts, _body) = request.into_parts();
+                let external = External::extract(&mut parts).await?;
 
-                Ok::<_, axum::response::Response>(Json(res))
+                let res = (this.f)(external).await;
+                Ok::<_, axum::response::Response>(res.respond(encoding))
             };
 
-            let router = api.axum_router.take().unwrap();
-            api.axum_router
-                .replace(router.route(&path, axum::routing::post(handler)));
+            axum::routing::post(handler)
         }
     }
 
-    impl<Response, F, Fut> ApiFn for HandlerAxum<(), Response, (), F>
+    impl<Response, M, F, Fut> ApiFn for HandlerAxum<(), (Response, M), (), F>
     where
-        Response: ResponseBound,
+        Response: ResponseBound<M>,
+        M: Send + Sync + 'static,
         Fut: std::future::Future<Output = Response> + Send + 'static,
         F: Sync + Send + 'static + Clone + Fn() -> Fut,
     {
-        fn register(self, api: &mut Api) {
-            let path = format!("/{}", function_name(&self.f));
-
-            let handler = move || async {
+        fn into_route(self) -> axum::routing::MethodRouter {
+            let handler = move |headers: HeaderMap| async move {
                 let this = self;
                 let res = (this.f)().await;
-                #[cfg(feature = "tagged-result")]
-                let res = res.boxed();
-
-                Ok::<_, axum::response::Response>(Json(res))
+                Ok::<_, axum::response::Response>(res.respond(Encoding::accepted(&headers)))
             };
 
-            let router = api.axum_router.take().unwrap();
-            api.axum_router
-                .replace(router.route(&path, axum::routing::post(handler)));
+            axum::routing::post(handler)
         }
     }
 
     macro_rules! impl_api_fn {
     ($($t:ident),* | $($a:tt),*) => {
-        impl<$($t,)* Response, External, F, Fut> ApiFn
-            for HandlerAxum<($($t,)*), Response, Axum<External>, F>
+        impl<$($t,)* Response, M, External, F, Fut> ApiFn
+            for HandlerAxum<($($t,)*), (Response, M), External, F>
         where
             $($t: Sync + Send + 'static + DeserializeOwned,)*
-            Response: ResponseBound,
-            External: Sync + Send + 'static + FromRequestParts<()>,
+            Response: ResponseBound<M>,
+            M: Send + Sync + 'static,
+            External: Extractor,
             Fut: std::future::Future<Output = Response> + Send + 'static,
-            F: Sync + Send + 'static + Clone + Fn($($t,)* Axum<External>) -> Fut,
+            F: Sync + Send + 'static + Clone + Fn($($t,)* External) -> Fut,
         {
-            fn register(self, api: &mut Api) {
-                let path = format!("/{}", function_name(&self.f));
-
+            fn into_route(self) -> axum::routing::MethodRouter {
                 let handler = move |request: axum::http::Request<axum::body::Body>| async {
                     let this = self;
 
+                    let encoding = Encoding::accepted(request.headers());
                     let (mut parts, body) = request.into_parts();
-                    let external = FromRequestParts::from_request_parts(&mut parts, &())
-                        .await
-                        .map_err(|e: <External as FromRequestParts<()>>::Rejection| {
-                            e.into_response()
-                        })?;
+                    let external = External::extract(&mut parts).await?;
+                    let parameters = parts.extensions.get::<Parameters>().cloned();
 
                     let request = axum::http::Request::from_parts(parts, body);
-                    let params: ($($t,)*) = Json::from_request(request, &())
-                        .await
-                        .map_err(|e| e.into_response())?
-                        .0;
-
-                    let res = (this.f)($(params.$a,)* Axum(external)).await;
-                    #[cfg(feature = "tagged-result")]
-                    let res = res.boxed();
+                    let params: ($($t,)*) = encoding::decode(request).await?;
+                    validate_arguments(parameters.as_ref(), &[$(&params.$a as &dyn Any),*])
+                        .map_err(crate::validate::reject)?;
 
-                    Ok::<_, axum::response::Response>(Json(res))
+                    let res = (this.f)($(params.$a,)* external).await;
+                    Ok::<_, axum::response::Response>(res.respond(encoding))
                 };
 
-                let router = api.axum_router.take().unwrap();
-                api.axum_router
-                    .replace(router.route(&path, axum::routing::post(handler)));
+                axum::routing::post(handler)
             }
         }
 
-        impl<$($t,)* Response, F, Fut> ApiFn
-        for HandlerAxum<($($t,)*), Response, (), F>
+        impl<$($t,)* Response, M, F, Fut> ApiFn
+        for HandlerAxum<($($t,)*), (Response, M), (), F>
         where
             $($t: Sync + Send + 'static + DeserializeOwned,)*
-            Response: ResponseBound,
+            Response: ResponseBound<M>,
+            M: Send + Sync + 'static,
             Fut: std::future::Future<Output = Response> + Send + 'static,
             F: Sync + Send + 'static + Clone + Fn($($t,)*) -> Fut,
         {
-            fn register(self, api: &mut Api) {
-                let path = format!("/{}", function_name(&self.f));
-
+            fn into_route(self) -> axum::routing::MethodRouter {
                 let handler = move |request: axum::http::Request<axum::body::Body>| async {
                     let this = self;
 
-                    let params: ($($t,)*) = Json::from_request(request, &())
-                        .await
-                        .map_err(|e| e.into_response())?
-                        .0;
+                    let encoding = Encoding::accepted(request.headers());
+                    let parameters = request.extensions().get::<Parameters>().cloned();
+                    let params: ($($t,)*) = encoding::decode(request).await?;
+                    validate_arguments(parameters.as_ref(), &[$(&params.$a as &dyn Any),*])
+                        .map_err(crate::validate::reject)?;
 
                     let res = (this.f)($(params.$a,)*).await;
-                    #[cfg(feature = "tagged-result")]
-                    let res = res.boxed();
-
-                    Ok::<_, axum::response::Response>(Json(res))
+                    Ok::<_, axum::response::Response>(res.respond(encoding))
                 };
 
-                let router = api.axum_router.take().unwrap();
-                api.axum_router
-                    .replace(router.route(&path, axum::routing::post(handler)));
+                axum::routing::post(handler)
             }
         }
         };
@@ -501,17 +2113,172 @@ fn type_name_of_val<T: ?Sized>(_val: &T) -> &'static str {
 #[derive(Debug, Clone)]
 pub struct TsFn {
     pub name: &'static str,
+    // `module_path!()` of the function
+    pub module_path: &'static str,
+    // `name` prefixed with the module path without the crate, `users.admin.ban`
+    pub nested_name: String,
+    // rust doc comments of the function
+    pub docs: Option<String>,
+    // `#[deprecated]` attribute of the function
+    pub deprecated: Option<Deprecation>,
+    // whether the last parameter is an `Axum<External>` or `Ctx<C>` extractor, which may read headers
+    pub axum_extractor: bool,
+    // `#[ts_export(layer = ..)]`, wraps the route of the function
+    #[cfg(feature = "axum-router")]
+    pub layer: Option<fn(axum::routing::MethodRouter) -> axum::routing::MethodRouter>,
+    // `#[ts_export(guard = ..)]`, checked in order before the function runs
+    #[cfg(feature = "axum-router")]
+    pub guards: Vec<Guard>,
     // .. -> type declaration in typescript
     pub type_declarations: BTreeMap<ts_rs::Id, String>,
-    // parameter name -> typescript type name with generics filled in
-    pub request_types: Vec<(&'static str, String)>,
+    pub request_types: Vec<TsParam>,
     // typescript type name with generics filled in
     pub response_type: String,
+    // the rust type of the response, `None` for downloads, see `Api::override_type`
+    pub response_type_id: Option<TypeId>,
+    // whether the function returns a `Download` or a `Result` of one, received as a `Blob`
+    pub download: bool,
+    // `#[ts_export(response_header = "..")]`, typed in the `withMeta` variant of the client
+    pub response_headers: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TsParam {
+    pub name: &'static str,
+    // typescript type name with generics filled in
+    pub ty: String,
+    // rust doc comments of the parameter
+    pub docs: Option<String>,
+    // `#[sensitive]`, the value is redacted in traces
+    pub sensitive: bool,
+    // an `Upload`, sent as a part of a `multipart/form-data` request
+    pub upload: bool,
+    // `#[header]`, `#[cookie]` or `#[query]`, sent outside of the arguments
+    pub source: Option<ParamSource>,
+    // the rust type, `None` for uploads, see `Api::override_type`
+    pub type_id: Option<TypeId>,
+}
+
+/// The type of a parameter of a function using `#[ts_export]`, added to its [`TsFn`].
+/// Implemented by types implementing `TS` and by `Upload`, which is sent as a file.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be the type of a parameter",
+    label = "doesn't implement `TS`",
+    note = "`Upload` parameters need the `multipart` feature of ts-rpc"
+)]
+pub trait ParamType {
+    fn add_to(ts_fn: &mut TsFn, param_name: &'static str);
+}
+
+impl<T: TS + 'static> ParamType for T {
+    fn add_to(ts_fn: &mut TsFn, param_name: &'static str) {
+        ts_fn.add_request_type::<T>(param_name);
+    }
+}
+
+#[cfg(feature = "multipart")]
+impl ParamType for Upload {
+    fn add_to(ts_fn: &mut TsFn, param_name: &'static str) {
+        ts_fn.add_upload_param(param_name);
+    }
+}
+
+/// Where a parameter is read from instead of the arguments, set by the `#[header("name")]`,
+/// `#[cookie("name")]` and `#[query("name")]` attributes of parameters in `#[ts_export]`.
+///
+/// The typescript client keeps them as typed parameters of the function. Values are parsed
+/// from text, so they are strings, numbers, booleans, unit enums or an `Option` of one.
+/// Browsers don't let `fetch` set cookies, so cookie parameters are left out of the client
+/// unless `Api::ts_cookie_params` is set, and the browser sends the cookies it stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamSource {
+    Header(&'static str),
+    Cookie(&'static str),
+    Query(&'static str),
+}
+
+impl ParamSource {
+    /// The name of the header, cookie or query parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParamSource::Header(name) | ParamSource::Cookie(name) | ParamSource::Query(name) => {
+                name
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Deprecation {
+    pub note: Option<String>,
+    pub since: Option<String>,
+    // http date after which the function may be removed, sent as the `Sunset` header
+    pub sunset: Option<String>,
 }
 
 pub struct LazyTsFn(pub &'static once_cell::sync::Lazy<TsFn>);
 inventory::collect!(LazyTsFn);
 
+/// The zero-sized marker `#[ts_export]` generates for a function, identifying it by its path.
+pub trait ProcedureMarker: 'static {
+    /// The full path of the function, `app::users::admin::ban`.
+    const PATH: &'static str;
+
+    fn ts_fn() -> &'static TsFn;
+
+    /// The type of the function item, unique to the function, which `register_axum` is
+    /// called with.
+    fn handler() -> std::any::TypeId;
+}
+
+/// A function using `#[ts_export]`, submitted to `inventory` by its [`ProcedureMarker`].
+pub struct Procedure {
+    pub path: &'static str,
+    pub ts_fn: fn() -> &'static TsFn,
+    pub handler: fn() -> std::any::TypeId,
+}
+inventory::collect!(Procedure);
+
+impl Procedure {
+    pub const fn of<M: ProcedureMarker>() -> Self {
+        Self {
+            path: M::PATH,
+            ts_fn: M::ts_fn,
+            handler: M::handler,
+        }
+    }
+}
+
+// type of the function item -> its procedure, without initializing the `TsFn`s
+#[cfg(feature = "axum-router")]
+static HANDLERS: once_cell::sync::Lazy<
+    std::collections::HashMap<std::any::TypeId, &'static Procedure>,
+> = once_cell::sync::Lazy::new(|| {
+    inventory::iter::<Procedure>()
+        .map(|procedure| ((procedure.handler)(), procedure))
+        .collect()
+});
+
+/// The `TsFn` of a function using `#[ts_export]`, by the type of the function item its marker
+/// submits.
+#[cfg(feature = "axum-router")]
+fn handler_ts_fn<F: 'static>() -> Option<&'static TsFn> {
+    HANDLERS
+        .get(&std::any::TypeId::of::<F>())
+        .map(|procedure| (procedure.ts_fn)())
+}
+
+/// Doc comments of a type and its fields, collected by `#[derive(TsDocs)]`.
+/// They are added to the type declarations in the typescript client.
+pub struct TypeDocs {
+    // typescript name of the type
+    pub name: &'static str,
+    pub docs: Option<&'static str>,
+    // typescript field name -> doc comment
+    pub fields: &'static [(&'static str, &'static str)],
+}
+inventory::collect!(TypeDocs);
+
 // use once_cell::sync::Lazy;
 
 // static __A: Lazy<&'static str> = Lazy::new(|| "hello");
@@ -523,16 +2290,165 @@ impl TsFn {
     pub fn new(name: &'static str) -> Self {
         Self {
             name,
+            module_path: "",
+            nested_name: name.to_string(),
+            docs: None,
+            deprecated: None,
+            axum_extractor: false,
+            #[cfg(feature = "axum-router")]
+            layer: None,
+            #[cfg(feature = "axum-router")]
+            guards: Vec::new(),
             type_declarations: Default::default(),
             request_types: Default::default(),
             response_type: Default::default(),
+            response_type_id: None,
+            download: false,
+            response_headers: Vec::new(),
         }
     }
 
-    pub fn add_request_type<T: TS>(&mut self, param_name: &'static str) {
+    pub fn add_request_type<T: TS + 'static>(&mut self, param_name: &'static str) {
         self.add_type_definitions::<T>();
-        self.request_types
-            .push((param_name, T::name_with_generics()));
+        self.request_types.push(TsParam {
+            name: param_name,
+            ty: T::name_with_generics(),
+            docs: None,
+            sensitive: false,
+            upload: false,
+            source: None,
+            type_id: Some(TypeId::of::<T>()),
+        });
+    }
+
+    /// Adds a parameter of type `T`, see [`ParamType`].
+    pub fn add_param<T: ParamType>(&mut self, param_name: &'static str) {
+        T::add_to(self, param_name);
+    }
+
+    /// Adds an `Upload` parameter, which the client sends as a file.
+    pub fn add_upload_param(&mut self, param_name: &'static str) {
+        self.request_types.push(TsParam {
+            name: param_name,
+            ty: "Blob | File".to_string(),
+            docs: None,
+            sensitive: false,
+            upload: true,
+            source: None,
+            type_id: None,
+        });
+    }
+
+    pub fn set_docs(&mut self, docs: &str) {
+        self.docs = Some(docs.to_string());
+    }
+
+    pub fn set_deprecated(&mut self, note: Option<&str>, since: Option<&str>) {
+        let deprecation = self.deprecated.get_or_insert_with(Default::default);
+        deprecation.note = note.map(str::to_string);
+        deprecation.since = since.map(str::to_string);
+    }
+
+    pub fn set_sunset(&mut self, sunset: &str) {
+        self.deprecated.get_or_insert_with(Default::default).sunset = Some(sunset.to_string());
+    }
+
+    pub fn set_sensitive(&mut self, param_name: &str) {
+        if let Some(param) = self.request_types.iter_mut().find(|p| p.name == param_name) {
+            param.sensitive = true;
+        }
+    }
+
+    pub fn add_response_header(&mut self, name: &'static str) {
+        self.response_headers.push(name);
+    }
+
+    pub fn set_param_source(&mut self, param_name: &str, source: ParamSource) {
+        if let Some(param) = self.request_types.iter_mut().find(|p| p.name == param_name) {
+            param.source = Some(source);
+        }
+    }
+
+    pub fn set_module_path(&mut self, module_path: &'static str) {
+        self.module_path = module_path;
+        self.nested_name = module_path
+            .split("::")
+            .skip(1)
+            .chain([self.name])
+            .collect::<Vec<_>>()
+            .join(".");
+    }
+
+    /// The full path of the function, `app::users::admin::ban`.
+    pub fn path(&self) -> String {
+        if self.module_path.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{}::{}", self.module_path, self.name)
+        }
+    }
+
+    /// The name the function is exported and served as, see `Api::nested_modules`.
+    pub fn exported_name(&self, nested_modules: bool) -> &str {
+        if nested_modules {
+            &self.nested_name
+        } else {
+            self.name
+        }
+    }
+
+    /// The last segment of dotted names like `users.get`, the name of the typescript function.
+    pub fn local_name(&self) -> &'static str {
+        self.name.rsplit('.').next().unwrap()
+    }
+
+    /// Names of the `#[ts_export(guard = ..)]` functions, empty without the `axum-router` feature.
+    pub fn guard_names(&self) -> Vec<&'static str> {
+        #[cfg(feature = "axum-router")]
+        return self.guards.iter().map(|g| g.name).collect();
+        #[cfg(not(feature = "axum-router"))]
+        Vec::new()
+    }
+
+    pub fn set_axum_extractor(&mut self) {
+        self.axum_extractor = true;
+    }
+
+    #[cfg(feature = "axum-router")]
+    pub fn set_layer(
+        &mut self,
+        layer: fn(axum::routing::MethodRouter) -> axum::routing::MethodRouter,
+    ) {
+        self.layer = Some(layer);
+    }
+
+    #[cfg(feature = "axum-router")]
+    pub fn add_guard(
+        &mut self,
+        name: &'static str,
+        check: fn(&(dyn std::any::Any + Send + Sync)) -> Option<Result<(), GuardError>>,
+    ) {
+        self.guards.push(Guard { name, check });
+    }
+
+    pub fn set_param_docs(&mut self, param_name: &str, docs: &str) {
+        if let Some(param) = self.request_types.iter_mut().find(|p| p.name == param_name) {
+            param.docs = Some(docs.to_string());
+        }
+    }
+
+    /// The ok and error types, if the response type is a `Result`.
+    pub fn result_types(&self) -> Option<(String, String)> {
+        match ts_type::parse_type(&self.response_type) {
+            Ok(ts_type::TsType::Reference(name, mut generics))
+                if name == "Result" && generics.len() == 2 =>
+            {
+                let err = generics.pop().unwrap();
+                let ok = generics.pop().unwrap();
+                Some((ok.to_string(), err.to_string()))
+            }
+            _ => None,
+        }
     }
 
     pub fn add_type_definitions<T: ts_rs::TS>(&mut self) {
@@ -547,9 +2463,24 @@ impl TsFn {
         );
     }
 
-    pub fn set_response_type<T: ts_rs::TS>(&mut self) {
+    pub fn set_response_type<T: ts_rs::TS + 'static>(&mut self) {
         self.add_type_definitions::<T>();
         self.response_type = T::name_with_generics();
+        self.response_type_id = Some(TypeId::of::<T>());
+    }
+
+    /// Sets the response to a `Download`, which the client receives as a file.
+    pub fn set_download_response(&mut self) {
+        self.response_type = "Blob".to_string();
+        self.download = true;
+    }
+
+    /// Sets the response to a `Result<Download, E>`, which the client receives as a `Result`
+    /// with the file as its `Ok`.
+    pub fn set_download_result_response<E: ts_rs::TS + 'static>(&mut self) {
+        self.add_type_definitions::<Result<(), E>>();
+        self.response_type = format!("Result<Blob, {}>", E::name_with_generics());
+        self.download = true;
     }
 }
 
@@ -569,12 +2500,17 @@ mod specialized_serialization {
 
     pub trait SpecializedSerialize {
         fn boxed(self) -> Box<dyn erased_serde::Serialize>;
+        fn is_err(&self) -> bool;
     }
 
     impl<T: serde::Serialize + NotResult + 'static> SpecializedSerialize for T {
         fn boxed(self) -> Box<dyn erased_serde::Serialize> {
             Box::new(self)
         }
+
+        fn is_err(&self) -> bool {
+            false
+        }
     }
 
     impl<T: serde::Serialize + 'static, E: serde::Serialize + 'static> SpecializedSerialize
@@ -587,6 +2523,10 @@ mod specialized_serialization {
             };
             Box::new(tagged_result)
         }
+
+        fn is_err(&self) -> bool {
+            std::result::Result::is_err(self)
+        }
     }
 }
 
@@ -0,0 +1,649 @@
+//! A machine readable description of the exported procedures and the detection of breaking
+//! changes between two versions of it.
+//!
+//! Backend and frontend are often deployed independently, so a client generated from an old
+//! manifest may talk to a server built from a new one. [`Manifest::compare`] reports every
+//! difference and whether it breaks such a client.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ts_type::{parse_declaration, parse_type, TsDeclaration, TsType};
+use crate::TsFn;
+
+/// Version of the manifest format, bumped on incompatible changes to it.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    // procedure name -> procedure
+    pub procedures: BTreeMap<String, Procedure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Procedure {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    pub params: Vec<Param>,
+    // the ok type if the function returns a `Result`
+    pub response: TsType,
+    // the error type if the function returns a `Result`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<TsType>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    // whether the function reads the request through an `Axum<External>` or `Ctx<C>` extractor
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub axum_extractor: bool,
+    // names of the `#[ts_export(guard = ..)]` functions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub guards: Vec<String>,
+    // type name -> declaration, for every type the procedure references
+    #[serde(default)]
+    pub types: BTreeMap<String, TsDeclaration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    pub ty: TsType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    // where the value is sent if not in the arguments, e.g. `{ "header": "x-tenant" }`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ParamSource>,
+}
+
+/// A parameter read from a header, cookie or query parameter, see [`crate::ParamSource`].
+/// Its value is sent as text, strings as they are and other values as json.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamSource {
+    Header(String),
+    Cookie(String),
+    Query(String),
+}
+
+impl From<crate::ParamSource> for ParamSource {
+    fn from(source: crate::ParamSource) -> Self {
+        match source {
+            crate::ParamSource::Header(name) => ParamSource::Header(name.to_string()),
+            crate::ParamSource::Cookie(name) => ParamSource::Cookie(name.to_string()),
+            crate::ParamSource::Query(name) => ParamSource::Query(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ParamSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamSource::Header(name) => write!(f, "the `{name}` header"),
+            ParamSource::Cookie(name) => write!(f, "the `{name}` cookie"),
+            ParamSource::Query(name) => write!(f, "the `{name}` query parameter"),
+        }
+    }
+}
+
+/// A difference between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub procedure: String,
+    // location inside the procedure, e.g. `params.user.email` or `response`
+    pub path: String,
+    // whether clients built against the old manifest may fail with the new one
+    pub breaking: bool,
+    pub message: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.breaking {
+            "breaking"
+        } else {
+            "compatible"
+        };
+        write!(f, "{kind}: {}", self.procedure)?;
+        if !self.path.is_empty() {
+            write!(f, " {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Manifest {
+    /// The manifest of `ts_fns`, paired with the names they are exported as.
+    pub fn new<'a>(ts_fns: impl IntoIterator<Item = (&'a str, &'a TsFn)>) -> Self {
+        let procedures = ts_fns
+            .into_iter()
+            .map(|(name, ts_fn)| (name.to_string(), Procedure::new(ts_fn)))
+            .collect();
+        Self {
+            version: MANIFEST_VERSION,
+            procedures,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() + "\n"
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Every difference between `self` and `new`, where `self` is the manifest the deployed
+    /// clients were built against.
+    pub fn compare(&self, new: &Manifest) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for (name, old_procedure) in self.procedures.iter() {
+            match new.procedures.get(name) {
+                Some(new_procedure) => {
+                    let mut comparison = Comparison {
+                        procedure: name,
+                        old: old_procedure,
+                        new: new_procedure,
+                        visited: BTreeSet::new(),
+                        changes: &mut changes,
+                    };
+                    comparison.procedure();
+                }
+                None => changes.push(Change {
+                    procedure: name.clone(),
+                    path: String::new(),
+                    breaking: true,
+                    message: "procedure was removed".to_string(),
+                }),
+            }
+        }
+        for name in new.procedures.keys() {
+            if !self.procedures.contains_key(name) {
+                changes.push(Change {
+                    procedure: name.clone(),
+                    path: String::new(),
+                    breaking: false,
+                    message: "procedure was added".to_string(),
+                });
+            }
+        }
+        changes
+    }
+}
+
+impl Procedure {
+    /// Types that can't be parsed are `unknown`, declarations that can't be parsed are left
+    /// out, see [`ApiIssue::UnsupportedType`](crate::ApiIssue::UnsupportedType).
+    pub fn new(ts_fn: &TsFn) -> Self {
+        let parse = |ty: &str| parse_type(ty).unwrap_or(TsType::Keyword("unknown".to_string()));
+        let params = ts_fn
+            .request_types
+            .iter()
+            .map(|p| Param {
+                name: p.name.to_string(),
+                ty: parse(&p.ty),
+                docs: p.docs.clone(),
+                source: p.source.map(ParamSource::from),
+            })
+            .collect();
+        let (response, error) = match ts_fn.result_types() {
+            Some((ok, err)) => (parse(&ok), Some(parse(&err))),
+            None => (parse(&ts_fn.response_type), None),
+        };
+        let types = ts_fn
+            .type_declarations
+            .values()
+            .filter_map(|d| parse_declaration(d).ok())
+            .map(|d| (d.name.clone(), d))
+            .collect();
+        Self {
+            docs: ts_fn.docs.clone(),
+            params,
+            response,
+            error,
+            deprecated: ts_fn.deprecated.is_some(),
+            axum_extractor: ts_fn.axum_extractor,
+            guards: ts_fn.guard_names().into_iter().map(String::from).collect(),
+            types,
+        }
+    }
+}
+
+/// Which way values of a type travel. Inputs must accept everything they accepted before,
+/// outputs must not produce anything the old clients don't understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Direction {
+    Input,
+    Output,
+}
+
+struct Comparison<'a> {
+    procedure: &'a str,
+    old: &'a Procedure,
+    new: &'a Procedure,
+    // pairs of type references already compared, recursive types would loop otherwise
+    visited: BTreeSet<(String, String, Direction)>,
+    changes: &'a mut Vec<Change>,
+}
+
+impl Comparison<'_> {
+    fn procedure(&mut self) {
+        let (old, new) = (self.old, self.new);
+
+        if !old.deprecated && new.deprecated {
+            self.push("", false, "procedure was deprecated".to_string());
+        }
+        for guard in new.guards.iter().filter(|g| !old.guards.contains(g)) {
+            self.push("", true, format!("guard `{guard}` was added"));
+        }
+        for guard in old.guards.iter().filter(|g| !new.guards.contains(g)) {
+            self.push("", false, format!("guard `{guard}` was removed"));
+        }
+
+        for (i, old_param) in old.params.iter().enumerate() {
+            match new.params.get(i) {
+                Some(new_param) => {
+                    let path = format!("params.{}", new_param.name);
+                    if old_param.name != new_param.name {
+                        // Arguments are sent positionally, the name doesn't matter on the wire.
+                        self.push(
+                            &path,
+                            false,
+                            format!("parameter `{}` was renamed", old_param.name),
+                        );
+                    }
+                    if old_param.source != new_param.source {
+                        let message = match &new_param.source {
+                            Some(source) => format!("parameter is now read from {source}"),
+                            None => "parameter is now read from the arguments".to_string(),
+                        };
+                        self.push(&path, true, message);
+                    }
+                    self.ty(&old_param.ty, &new_param.ty, Direction::Input, &path);
+                }
+                None => self.push(
+                    &format!("params.{}", old_param.name),
+                    true,
+                    "parameter was removed".to_string(),
+                ),
+            }
+        }
+        for new_param in new.params.iter().skip(old.params.len()) {
+            self.push(
+                &format!("params.{}", new_param.name),
+                true,
+                "parameter was added".to_string(),
+            );
+        }
+
+        self.ty(&old.response, &new.response, Direction::Output, "response");
+        match (&old.error, &new.error) {
+            (Some(old_error), Some(new_error)) => {
+                self.ty(old_error, new_error, Direction::Output, "error")
+            }
+            (None, Some(_)) => self.push("response", true, "now returns a `Result`".to_string()),
+            (Some(_), None) => {
+                self.push("response", true, "no longer returns a `Result`".to_string())
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn push(&mut self, path: &str, breaking: bool, message: String) {
+        self.changes.push(Change {
+            procedure: self.procedure.to_string(),
+            path: path.to_string(),
+            breaking,
+            message,
+        });
+    }
+
+    fn ty(&mut self, old: &TsType, new: &TsType, direction: Direction, path: &str) {
+        let old_resolved = resolve(old, &self.old.types);
+        let new_resolved = resolve(new, &self.new.types);
+        if old_resolved.is_some() || new_resolved.is_some() {
+            if self
+                .visited
+                .insert((old.to_string(), new.to_string(), direction))
+            {
+                self.ty(
+                    old_resolved.as_ref().unwrap_or(old),
+                    new_resolved.as_ref().unwrap_or(new),
+                    direction,
+                    path,
+                );
+            }
+            return;
+        }
+
+        if matches!(old, TsType::Union(_)) || matches!(new, TsType::Union(_)) {
+            self.variants(variants(old), variants(new), direction, path);
+            return;
+        }
+
+        match (old, new) {
+            (TsType::Keyword(o), TsType::Keyword(n)) if o == n => {}
+            (TsType::Literal(o), TsType::Literal(n)) if o == n => {}
+            (_, TsType::Keyword(k)) if direction == Direction::Input && is_any(k) => self.push(
+                path,
+                false,
+                format!("now accepts any value instead of `{old}`"),
+            ),
+            (TsType::Keyword(k), _) if direction == Direction::Output && is_any(k) => self.push(
+                path,
+                false,
+                format!("now returns `{new}` instead of any value"),
+            ),
+            (TsType::Array(o), TsType::Array(n)) => self.ty(o, n, direction, &format!("{path}[]")),
+            (TsType::Index(_, o), TsType::Index(_, n)) => {
+                self.ty(o, n, direction, &format!("{path}[key]"))
+            }
+            (TsType::Tuple(o), TsType::Tuple(n)) if o.len() == n.len() => {
+                for (i, (o, n)) in o.iter().zip(n).enumerate() {
+                    self.ty(o, n, direction, &format!("{path}[{i}]"));
+                }
+            }
+            (TsType::Intersection(o), TsType::Intersection(n)) if o.len() == n.len() => {
+                for (o, n) in o.iter().zip(n) {
+                    self.ty(o, n, direction, path);
+                }
+            }
+            (TsType::Reference(o, o_args), TsType::Reference(n, n_args))
+                if o == n && o_args.len() == n_args.len() =>
+            {
+                for (o, n) in o_args.iter().zip(n_args) {
+                    self.ty(o, n, direction, path);
+                }
+            }
+            (TsType::Object(o), TsType::Object(n)) => self.fields(o, n, direction, path),
+            _ => self.push(path, true, format!("type changed from `{old}` to `{new}`")),
+        }
+    }
+
+    fn fields(
+        &mut self,
+        old: &[crate::ts_type::TsField],
+        new: &[crate::ts_type::TsField],
+        direction: Direction,
+        path: &str,
+    ) {
+        let join = |name: &str| {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{path}.{name}")
+            }
+        };
+
+        for old_field in old {
+            let field_path = join(&old_field.name);
+            let Some(new_field) = new.iter().find(|f| f.name == old_field.name) else {
+                // Unknown fields are ignored when deserializing, but old clients may rely on
+                // receiving a field.
+                let breaking = direction == Direction::Output && !old_field.optional;
+                self.push(&field_path, breaking, "field was removed".to_string());
+                continue;
+            };
+
+            match direction {
+                // A missing `Option` field deserializes to `None`.
+                Direction::Input
+                    if (old_field.optional || old_field.ty.is_nullable())
+                        && !(new_field.optional || new_field.ty.is_nullable()) =>
+                {
+                    self.push(&field_path, true, "field was made required".to_string())
+                }
+                Direction::Output if !old_field.optional && new_field.optional => {
+                    self.push(&field_path, true, "field was made optional".to_string())
+                }
+                _ => {}
+            }
+            self.ty(&old_field.ty, &new_field.ty, direction, &field_path);
+        }
+
+        for new_field in new {
+            if old.iter().any(|f| f.name == new_field.name) {
+                continue;
+            }
+            let required = !(new_field.optional || new_field.ty.is_nullable());
+            if direction == Direction::Input && required {
+                self.push(
+                    &join(&new_field.name),
+                    true,
+                    "required field was added".to_string(),
+                );
+            } else {
+                self.push(&join(&new_field.name), false, "field was added".to_string());
+            }
+        }
+    }
+
+    fn variants(&mut self, old: &[TsType], new: &[TsType], direction: Direction, path: &str) {
+        let mut unmatched: Vec<&TsType> = new.iter().collect();
+        for old_variant in old {
+            let key = variant_key(old_variant);
+            match unmatched.iter().position(|n| variant_key(n) == key) {
+                Some(i) => {
+                    let new_variant = unmatched.remove(i);
+                    self.ty(old_variant, new_variant, direction, path);
+                }
+                None => match direction {
+                    Direction::Input => {
+                        self.push(path, true, format!("`{old_variant}` is no longer accepted"))
+                    }
+                    Direction::Output => self.push(
+                        path,
+                        false,
+                        format!("`{old_variant}` is no longer returned"),
+                    ),
+                },
+            }
+        }
+        for new_variant in unmatched {
+            match direction {
+                Direction::Input => {
+                    self.push(path, false, format!("`{new_variant}` is now accepted"))
+                }
+                Direction::Output => {
+                    self.push(path, true, format!("`{new_variant}` may now be returned"))
+                }
+            }
+        }
+    }
+}
+
+/// The declared type `ty` refers to, with its generics filled in.
+fn resolve(ty: &TsType, types: &BTreeMap<String, TsDeclaration>) -> Option<TsType> {
+    match ty {
+        TsType::Reference(name, args) => types
+            .get(name)
+            .map(|decl| decl.ty.substitute(&decl.generics, args)),
+        _ => None,
+    }
+}
+
+fn variants(ty: &TsType) -> &[TsType] {
+    match ty {
+        TsType::Union(variants) => variants,
+        _ => std::slice::from_ref(ty),
+    }
+}
+
+/// Identifies a variant of a union between two versions, e.g. the tag of an enum variant.
+fn variant_key(ty: &TsType) -> String {
+    match ty {
+        TsType::Keyword(k) => k.clone(),
+        TsType::Literal(l) => l.clone(),
+        TsType::Array(_) => "Array".to_string(),
+        TsType::Tuple(ts) => format!("[{}]", ts.len()),
+        TsType::Index(..) => "{ [key] }".to_string(),
+        TsType::Reference(name, _) => name.clone(),
+        TsType::Union(_) | TsType::Intersection(_) => ty.to_string(),
+        TsType::Object(fields) => {
+            // internally tagged `{ type: "A", ... }` or externally tagged `{ A: ... }`
+            match fields.iter().find(|f| matches!(f.ty, TsType::Literal(_))) {
+                Some(tag) => format!("{{ {}: {} }}", tag.name, tag.ty),
+                None if fields.len() == 1 => format!("{{ {} }}", fields[0].name),
+                None => "{}".to_string(),
+            }
+        }
+    }
+}
+
+fn is_any(keyword: &str) -> bool {
+    keyword == "any" || keyword == "unknown"
+}
+
+#[test]
+fn test_compare() {
+    fn procedure(params: &[&str], response: &str, types: &[&str]) -> Procedure {
+        Procedure {
+            docs: None,
+            params: params
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| Param {
+                    name: format!("p{i}"),
+                    ty: parse_type(ty).unwrap(),
+                    docs: None,
+                    source: None,
+                })
+                .collect(),
+            response: parse_type(response).unwrap(),
+            error: None,
+            deprecated: false,
+            axum_extractor: false,
+            guards: Vec::new(),
+            types: types
+                .iter()
+                .map(|d| parse_declaration(d).unwrap())
+                .map(|d| (d.name.clone(), d))
+                .collect(),
+        }
+    }
+    fn manifest(procedures: Vec<(&str, Procedure)>) -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            procedures: procedures
+                .into_iter()
+                .map(|(n, p)| (n.to_string(), p))
+                .collect(),
+        }
+    }
+    fn changes(old: Procedure, new: Procedure) -> Vec<String> {
+        manifest(vec![("f", old)])
+            .compare(&manifest(vec![("f", new)]))
+            .iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    let old = manifest(vec![("a", procedure(&[], "null", &[]))]);
+    let new = manifest(vec![("b", procedure(&[], "null", &[]))]);
+    assert_eq!(
+        old.compare(&new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>(),
+        [
+            "breaking: a: procedure was removed",
+            "compatible: b: procedure was added"
+        ]
+    );
+
+    assert_eq!(
+        changes(
+            procedure(&["string"], "null", &[]),
+            procedure(&["string", "number"], "null", &[])
+        ),
+        ["breaking: f params.p1: parameter was added"]
+    );
+
+    let mut header = procedure(&["string"], "null", &[]);
+    header.params[0].source = Some(ParamSource::Header("x-tenant".to_string()));
+    assert_eq!(
+        changes(procedure(&["string"], "null", &[]), header.clone()),
+        ["breaking: f params.p0: parameter is now read from the `x-tenant` header"]
+    );
+    assert_eq!(
+        serde_json::to_value(&header.params[0].source).unwrap(),
+        serde_json::json!({ "header": "x-tenant" })
+    );
+
+    let status_old = r#"type Status = "Active" | "Banned";"#;
+    let status_new = r#"type Status = "Active";"#;
+    assert_eq!(
+        changes(
+            procedure(&["Status"], "Status", &[status_old]),
+            procedure(&["Status"], "Status", &[status_new])
+        ),
+        [
+            r#"breaking: f params.p0: `"Banned"` is no longer accepted"#,
+            r#"compatible: f response: `"Banned"` is no longer returned"#,
+        ]
+    );
+
+    let user_old = "type User = { name: string, email?: string, age: number | null, };";
+    let user_new = "type User = { name: string, email: string, nick: string, };";
+    assert_eq!(
+        changes(
+            procedure(&["User"], "User", &[user_old]),
+            procedure(&["User"], "User", &[user_new])
+        ),
+        [
+            "breaking: f params.p0.email: field was made required",
+            "compatible: f params.p0.age: field was removed",
+            "breaking: f params.p0.nick: required field was added",
+            "breaking: f response.age: field was removed",
+            "compatible: f response.nick: field was added",
+        ]
+    );
+
+    assert_eq!(
+        changes(
+            procedure(&["string"], "string | null", &[]),
+            procedure(&["string | null"], "string", &[])
+        ),
+        [
+            "compatible: f params.p0: `null` is now accepted",
+            "compatible: f response: `null` is no longer returned",
+        ]
+    );
+
+    let mut guarded = procedure(&[], "null", &[]);
+    guarded.guards.push("require_admin".to_string());
+    assert_eq!(
+        changes(procedure(&[], "null", &[]), guarded),
+        ["breaking: f: guard `require_admin` was added"]
+    );
+
+    // Recursive types terminate.
+    let tree = "type Tree = { children: Array<Tree>, };";
+    assert!(changes(
+        procedure(&["Tree"], "Tree", &[tree]),
+        procedure(&["Tree"], "Tree", &[tree])
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_unsupported_type() {
+    let mut ts_fn = TsFn::new("f");
+    ts_fn.add_request_type::<String>("a");
+    ts_fn.set_response_type::<String>();
+    ts_fn.request_types[0].ty = "keyof User".to_string();
+    ts_fn.type_declarations.insert(
+        <String as ts_rs::TS>::id(),
+        "type Keys = keyof User;".to_string(),
+    );
+
+    let manifest = Manifest::new([("f", &ts_fn)]);
+    let procedure = &manifest.procedures["f"];
+    assert_eq!(
+        procedure.params[0].ty,
+        TsType::Keyword("unknown".to_string())
+    );
+    assert_eq!(procedure.response, TsType::Keyword("string".to_string()));
+    assert!(procedure.types.is_empty());
+}
@@ -0,0 +1,43 @@
+//! Per procedure metrics, see the `metrics` feature.
+//!
+//! Recorded through the [`metrics`] facade, so they go to whichever recorder is installed:
+//! - `ts_rpc_requests_total`: counter of calls
+//! - `ts_rpc_request_duration_seconds`: histogram of call latencies
+//!
+//! Both are labeled with `procedure` and `outcome`, one of `ok`, `err` for an `Err` result,
+//! `rejected` if the `Axum<_>` extractor rejected the request, `invalid_arguments` if the
+//! arguments couldn't be deserialized and `failed` for other error responses.
+
+use std::sync::{Arc, Once};
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::outcome::Outcome;
+
+pub(crate) const REQUESTS_TOTAL: &str = "ts_rpc_requests_total";
+pub(crate) const REQUEST_DURATION: &str = "ts_rpc_request_duration_seconds";
+
+pub(crate) async fn measure(procedure: Arc<str>, request: Request, next: Next) -> Response {
+    static DESCRIBE: Once = Once::new();
+    DESCRIBE.call_once(|| {
+        metrics::describe_counter!(REQUESTS_TOTAL, "Number of procedure calls");
+        metrics::describe_histogram!(
+            REQUEST_DURATION,
+            metrics::Unit::Seconds,
+            "Latency of procedure calls"
+        );
+    });
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let labels = vec![
+        metrics::Label::new("procedure", procedure),
+        metrics::Label::new("outcome", Outcome::of(&response).as_str()),
+    ];
+    metrics::counter!(REQUESTS_TOTAL, labels.clone()).increment(1);
+    metrics::histogram!(REQUEST_DURATION, labels).record(start.elapsed().as_secs_f64());
+    response
+}
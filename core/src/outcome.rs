@@ -0,0 +1,220 @@
+//! How a procedure call ended, shared by the `tracing` and `metrics` features.
+
+use axum::response::Response;
+
+/// Stored in the response extensions of every procedure call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// The function returned, with `Ok` if it returns a `Result`.
+    Ok,
+    /// The function returned an `Err`.
+    Err,
+    /// The `Axum<External>` extractor rejected the request.
+    Rejected,
+    /// The arguments couldn't be deserialized.
+    InvalidArguments,
+    /// An error response not produced by the handler, e.g. by a layer.
+    Failed,
+}
+
+impl Outcome {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Err => "err",
+            Outcome::Rejected => "rejected",
+            Outcome::InvalidArguments => "invalid_arguments",
+            Outcome::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn of(response: &Response) -> Self {
+        response
+            .extensions()
+            .get::<Outcome>()
+            .copied()
+            .unwrap_or(Outcome::Failed)
+    }
+}
+
+/// Marks a rejection produced by the handler.
+pub(crate) fn reject(rejection: impl axum::response::IntoResponse, outcome: Outcome) -> Response {
+    let mut response = rejection.into_response();
+    response.extensions_mut().insert(outcome);
+    response
+}
+
+/// Marks a response the handler built from the return value of the function, unless encoding
+/// it failed.
+pub(crate) fn respond(mut response: Response, is_err: bool) -> Response {
+    if response.status().is_success() {
+        let outcome = if is_err { Outcome::Err } else { Outcome::Ok };
+        response.extensions_mut().insert(outcome);
+    }
+    response
+}
+
+/// Whether a value serializes as the `Err` of a `Result`, stopping at its first call to the
+/// serializer instead of serializing it.
+#[cfg(not(feature = "tagged-result"))]
+pub(crate) fn is_err<T: serde::Serialize + ?Sized>(value: &T) -> bool {
+    value.serialize(ResultProbe).is_ok()
+}
+
+#[cfg(not(feature = "tagged-result"))]
+struct ResultProbe;
+
+#[cfg(not(feature = "tagged-result"))]
+#[derive(Debug)]
+struct NotErr;
+
+#[cfg(not(feature = "tagged-result"))]
+impl std::fmt::Display for NotErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "not an `Err`")
+    }
+}
+
+#[cfg(not(feature = "tagged-result"))]
+impl std::error::Error for NotErr {}
+
+#[cfg(not(feature = "tagged-result"))]
+impl serde::ser::Error for NotErr {
+    fn custom<T: std::fmt::Display>(_msg: T) -> Self {
+        NotErr
+    }
+}
+
+#[cfg(not(feature = "tagged-result"))]
+macro_rules! not_err {
+    ($($method:ident($($ty:ty),*);)*) => {
+        $(fn $method(self, $(_: $ty),*) -> Result<Self::Ok, NotErr> {
+            Err(NotErr)
+        })*
+    };
+}
+
+#[cfg(not(feature = "tagged-result"))]
+impl serde::Serializer for ResultProbe {
+    type Ok = ();
+    type Error = NotErr;
+    type SerializeSeq = serde::ser::Impossible<(), NotErr>;
+    type SerializeTuple = serde::ser::Impossible<(), NotErr>;
+    type SerializeTupleStruct = serde::ser::Impossible<(), NotErr>;
+    type SerializeTupleVariant = serde::ser::Impossible<(), NotErr>;
+    type SerializeMap = serde::ser::Impossible<(), NotErr>;
+    type SerializeStruct = serde::ser::Impossible<(), NotErr>;
+    type SerializeStructVariant = serde::ser::Impossible<(), NotErr>;
+
+    // `Result` serializes as `serialize_newtype_variant("Result", 1, "Err", err)`
+    fn serialize_newtype_variant<T: serde::Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<(), NotErr> {
+        match (name, variant) {
+            ("Result", "Err") => Ok(()),
+            _ => Err(NotErr),
+        }
+    }
+
+    not_err! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T: serde::Serialize + ?Sized>(self, _value: &T) -> Result<(), NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_newtype_struct<T: serde::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<(), NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, NotErr> {
+        Err(NotErr)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NotErr> {
+        Err(NotErr)
+    }
+}
+
+#[cfg(not(feature = "tagged-result"))]
+#[test]
+fn test_is_err() {
+    #[derive(serde::Serialize)]
+    struct Response {
+        err: Result<(), String>,
+    }
+
+    assert!(is_err(&Err::<u32, _>("no")));
+    assert!(!is_err(&Ok::<_, String>(1)));
+    assert!(!is_err(&Response {
+        err: Err("no".into())
+    }));
+    assert!(!is_err(&Some(Err::<(), _>(()))));
+    assert!(is_err(&crate::WithHeaders::new(Err::<(), _>(()))));
+}
@@ -0,0 +1,175 @@
+//! Generates runtime validators for the typescript client, see `ts/validate.ts`.
+
+use std::collections::BTreeMap;
+
+use crate::ts_type::{
+    field_name, parse_declaration, parse_type, TsDeclaration, TsField, TsType,
+};
+use crate::validate::{self, Constraint, TypeConstraints};
+
+pub(crate) struct Schemas {
+    // declaration name -> parsed declaration and the constraints of its type
+    declarations: BTreeMap<String, (TsDeclaration, Option<&'static TypeConstraints>)>,
+}
+
+impl Schemas {
+    /// Declarations which can't be parsed are skipped, references to them accept any value.
+    pub(crate) fn new(declarations: &BTreeMap<ts_rs::Id, String>) -> Self {
+        Self {
+            declarations: declarations
+                .iter()
+                .filter_map(|(id, d)| {
+                    let d = parse_declaration(d).ok()?;
+                    Some((d.name.clone(), (d, validate::constraints(id))))
+                })
+                .collect(),
+        }
+    }
+
+    /// An `__schema` namespace holding one schema per declaration.
+    /// Generic declarations become functions from schemas to a schema.
+    pub(crate) fn namespace(&self) -> String {
+        let mut namespace = String::from("export namespace __schema {\n");
+        for (decl, constraints) in self.declarations.values() {
+            let expr = match (&decl.ty, constraints) {
+                (TsType::Object(fields), Some(constraints)) => {
+                    self.object(fields, &decl.generics, "", constraints.fields)
+                }
+                _ => self.expr(&decl.ty, &decl.generics, ""),
+            };
+            if decl.generics.is_empty() {
+                namespace += &format!(
+                    "        export const {}: __validate.Schema = {};\n",
+                    decl.name, expr
+                );
+            } else {
+                let params = decl
+                    .generics
+                    .iter()
+                    .map(|g| format!("{g}: __validate.Schema"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                namespace += &format!(
+                    "        export const {} = ({params}): __validate.Schema => {};\n",
+                    decl.name, expr
+                );
+            }
+        }
+        namespace += "    }";
+        namespace
+    }
+
+    /// The schema of a type used outside of the declarations, e.g. a parameter type.
+    /// References to declarations are qualified with `prefix`.
+    pub(crate) fn type_expr(&self, ty: &str, prefix: &str) -> String {
+        match parse_type(ty) {
+            Ok(ty) => self.expr(&ty, &[], prefix),
+            Err(_) => "__validate.any".to_string(),
+        }
+    }
+
+    /// Fields with constraints of `#[derive(Validate)]` check them after their type.
+    fn object(
+        &self,
+        fields: &[TsField],
+        generics: &[String],
+        prefix: &str,
+        constraints: &[(&str, &[Constraint])],
+    ) -> String {
+        let fields = fields
+            .iter()
+            .map(|f| {
+                let mut expr = self.expr(&f.ty, generics, prefix);
+                if let Some((_, constraints)) = constraints.iter().find(|(name, _)| *name == f.name) {
+                    expr = format!("__validate.constrained({expr}, {})", ts_constraints(constraints));
+                }
+                format!("{}: [{expr}, {}]", field_name(&f.name), f.optional)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("__validate.object({{ {fields} }})")
+    }
+
+    fn expr(&self, ty: &TsType, generics: &[String], prefix: &str) -> String {
+        let list = |types: &[TsType]| {
+            types
+                .iter()
+                .map(|t| self.expr(t, generics, prefix))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match ty {
+            TsType::Keyword(k) => match k.as_str() {
+                "string" | "number" | "boolean" | "bigint" | "never" => format!("__validate.{k}"),
+                "null" => "__validate.literal(null)".to_string(),
+                "undefined" | "void" => "__validate.literal(undefined)".to_string(),
+                _ => "__validate.any".to_string(),
+            },
+            TsType::Literal(l) => format!("__validate.literal({l})"),
+            TsType::Array(t) => format!("__validate.array({})", self.expr(t, generics, prefix)),
+            TsType::Tuple(ts) => format!("__validate.tuple([{}])", list(ts)),
+            TsType::Union(ts) => format!("__validate.union([{}])", list(ts)),
+            TsType::Intersection(ts) => format!("__validate.intersection([{}])", list(ts)),
+            TsType::Index(_, v) => format!("__validate.record({})", self.expr(v, generics, prefix)),
+            TsType::Object(fields) => self.object(fields, generics, prefix, &[]),
+            TsType::Reference(name, args) if args.is_empty() && generics.contains(name) => {
+                name.clone()
+            }
+            TsType::Reference(name, args) if name == "Record" && args.len() == 2 => {
+                format!(
+                    "__validate.record({})",
+                    self.expr(&args[1], generics, prefix)
+                )
+            }
+            TsType::Reference(name, args) => match self.declarations.get(name) {
+                Some((decl, _)) if decl.generics.is_empty() => {
+                    format!("__validate.lazy(() => {prefix}{name})")
+                }
+                Some(_) => format!("__validate.lazy(() => {prefix}{name}({}))", list(args)),
+                None => "__validate.any".to_string(),
+            },
+        }
+    }
+}
+
+/// The constraints of a field as a typescript object, see `Constraints` in `ts/validate.ts`.
+pub(crate) fn ts_constraints(constraints: &[Constraint]) -> String {
+    let properties = constraints
+        .iter()
+        .map(Constraint::ts_properties)
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{{ {properties} }}")
+}
+
+#[test]
+fn test_schemas() {
+    use ts_rs::TS;
+
+    // keyed by the ids of any types
+    let declarations = BTreeMap::from([
+        (
+            u8::id(),
+            "type Password = { password: string, hint?: string | null, };".to_string(),
+        ),
+        (u16::id(), "type ReturnType<T> = { inner: T, };".to_string()),
+    ]);
+    let schemas = Schemas::new(&declarations);
+
+    assert_eq!(
+        schemas.type_expr("ReturnType<Password>", "login.__schema."),
+        "__validate.lazy(() => login.__schema.ReturnType(__validate.lazy(() => login.__schema.Password)))"
+    );
+    assert_eq!(
+        schemas.type_expr("Array<Unknown>", ""),
+        "__validate.array(__validate.any)"
+    );
+    assert!(schemas.namespace().contains(
+        "export const Password: __validate.Schema = __validate.object({ password: [__validate.string, false], hint: [__validate.union([__validate.string, __validate.literal(null)]), true] });"
+    ));
+    assert!(schemas.namespace().contains(
+        "export const ReturnType = (T: __validate.Schema): __validate.Schema => __validate.object({ inner: [T, false] });"
+    ));
+}
@@ -0,0 +1,75 @@
+/**
+ * A converter returns the value with the values of overridden types in it converted,
+ * e.g. revived as `Date` objects. Arrays and objects are copied, never modified.
+ */
+export type Convert = (value: any) => any;
+
+export const identity: Convert = (value) => value;
+
+/**
+ * Converts values with a function given to `TypeOverride::revive` or `TypeOverride::serialize`.
+ */
+export const custom =
+  (convert: (value: any) => any): Convert =>
+  (value) =>
+    value === null || value === undefined ? value : convert(value);
+
+export const array =
+  (item: Convert): Convert =>
+  (value) =>
+    Array.isArray(value) ? value.map((v) => item(v)) : value;
+
+export const tuple =
+  (items: Convert[]): Convert =>
+  (value) =>
+    Array.isArray(value) ? value.map((v, i) => (items[i] ?? identity)(v)) : value;
+
+const isObject = (value: any): boolean =>
+  typeof value === 'object' && value !== null && !Array.isArray(value);
+
+/**
+ * Converts the fields with a converter, only if they are present.
+ */
+export const object =
+  (fields: Record<string, Convert>): Convert =>
+  (value) => {
+    if (!isObject(value)) {
+      return value;
+    }
+    const result = { ...value };
+    for (const [key, convert] of Object.entries(fields)) {
+      if (key in result) {
+        result[key] = convert(result[key]);
+      }
+    }
+    return result;
+  };
+
+export const record =
+  (item: Convert): Convert =>
+  (value) => {
+    if (!isObject(value)) {
+      return value;
+    }
+    const result: Record<string, any> = {};
+    for (const [key, v] of Object.entries(value)) {
+      result[key] = item(v);
+    }
+    return result;
+  };
+
+/**
+ * Converts the value of a union or intersection with the converters of all its parts in turn.
+ */
+export const all =
+  (parts: Convert[]): Convert =>
+  (value) =>
+    parts.reduce((value, convert) => convert(value), value);
+
+/**
+ * Defers looking up a converter, so declarations can reference each other in any order.
+ */
+export const lazy =
+  (get: () => Convert): Convert =>
+  (value) =>
+    get()(value);
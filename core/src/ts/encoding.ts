@@ -0,0 +1,506 @@
+// MessagePack and CBOR encodings for `createClient`, matching the `msgpack` and `cbor` features
+// of the server. Values have the same shape as in JSON: `undefined` becomes `null`, objects with
+// `toJSON` are encoded as its result, byte strings are decoded as arrays of numbers and integers
+// outside of the safe range lose precision. It avoids `bigint`, which needs ES2020.
+
+class Writer {
+  private bytes = new Uint8Array(256);
+  private view = new DataView(this.bytes.buffer);
+  private length = 0;
+
+  private reserve(n: number): number {
+    if (this.length + n > this.bytes.length) {
+      let size = this.bytes.length * 2;
+      while (size < this.length + n) {
+        size *= 2;
+      }
+      const bytes = new Uint8Array(size);
+      bytes.set(this.bytes);
+      this.bytes = bytes;
+      this.view = new DataView(bytes.buffer);
+    }
+    const offset = this.length;
+    this.length += n;
+    return offset;
+  }
+
+  u8(value: number): void {
+    this.bytes[this.reserve(1)] = value;
+  }
+
+  u16(value: number): void {
+    this.view.setUint16(this.reserve(2), value);
+  }
+
+  u32(value: number): void {
+    this.view.setUint32(this.reserve(4), value);
+  }
+
+  // two's complement of a safe integer, the high word is negative for negative values
+  i64(value: number): void {
+    const high = Math.floor(value / 2 ** 32);
+    const offset = this.reserve(8);
+    this.view.setInt32(offset, high);
+    this.view.setUint32(offset + 4, value - high * 2 ** 32);
+  }
+
+  f64(value: number): void {
+    this.view.setFloat64(this.reserve(8), value);
+  }
+
+  raw(bytes: Uint8Array): void {
+    this.bytes.set(bytes, this.reserve(bytes.length));
+  }
+
+  finish(): Uint8Array {
+    return this.bytes.slice(0, this.length);
+  }
+}
+
+class Reader {
+  private offset = 0;
+  private readonly view: DataView;
+
+  constructor(private readonly bytes: Uint8Array) {
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  private advance(n: number): number {
+    if (this.offset + n > this.bytes.length) {
+      throw new Error('unexpected end of input');
+    }
+    const offset = this.offset;
+    this.offset += n;
+    return offset;
+  }
+
+  u8(): number {
+    return this.bytes[this.advance(1)];
+  }
+
+  peek(): number {
+    return this.bytes[this.offset];
+  }
+
+  u16(): number {
+    return this.view.getUint16(this.advance(2));
+  }
+
+  u32(): number {
+    return this.view.getUint32(this.advance(4));
+  }
+
+  u64(): number {
+    const offset = this.advance(8);
+    return this.view.getUint32(offset) * 2 ** 32 + this.view.getUint32(offset + 4);
+  }
+
+  i8(): number {
+    return this.view.getInt8(this.advance(1));
+  }
+
+  i16(): number {
+    return this.view.getInt16(this.advance(2));
+  }
+
+  i32(): number {
+    return this.view.getInt32(this.advance(4));
+  }
+
+  i64(): number {
+    const offset = this.advance(8);
+    return this.view.getInt32(offset) * 2 ** 32 + this.view.getUint32(offset + 4);
+  }
+
+  f16(): number {
+    const half = this.u16();
+    const exponent = (half >> 10) & 0x1f;
+    const fraction = half & 0x3ff;
+    const sign = half & 0x8000 ? -1 : 1;
+    if (exponent === 0) {
+      return sign * fraction * 2 ** -24;
+    }
+    if (exponent === 0x1f) {
+      return fraction ? NaN : sign * Infinity;
+    }
+    return sign * (1 + fraction / 1024) * 2 ** (exponent - 15);
+  }
+
+  f32(): number {
+    return this.view.getFloat32(this.advance(4));
+  }
+
+  f64(): number {
+    return this.view.getFloat64(this.advance(8));
+  }
+
+  raw(n: number): Uint8Array {
+    const offset = this.advance(n);
+    return this.bytes.subarray(offset, offset + n);
+  }
+
+  end(): void {
+    if (this.offset !== this.bytes.length) {
+      throw new Error('unexpected trailing bytes');
+    }
+  }
+}
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder();
+
+// The value as it would be serialized by `JSON.stringify`.
+const toJSON = (value: any): any => {
+  return value !== null && typeof value === 'object' && typeof value.toJSON === 'function'
+    ? value.toJSON()
+    : value;
+};
+
+// The entries `JSON.stringify` would serialize.
+const entries = (value: object): [string, any][] => {
+  return Object.entries(value).filter(([_, v]) => v !== undefined && typeof v !== 'function');
+};
+
+// `bigint`s are encoded as numbers
+const toNumber = (value: any): any => {
+  return typeof value === 'bigint' ? Number(value) : value;
+};
+
+const encodeMsgpack = (writer: Writer, value: any): void => {
+  value = toNumber(toJSON(value));
+  if (value === null || value === undefined) {
+    writer.u8(0xc0);
+  } else if (typeof value === 'boolean') {
+    writer.u8(value ? 0xc3 : 0xc2);
+  } else if (Number.isSafeInteger(value)) {
+    if (value >= 0) {
+      if (value < 0x80) {
+        writer.u8(value);
+      } else if (value <= 0xff) {
+        writer.u8(0xcc);
+        writer.u8(value);
+      } else if (value <= 0xffff) {
+        writer.u8(0xcd);
+        writer.u16(value);
+      } else if (value <= 0xffffffff) {
+        writer.u8(0xce);
+        writer.u32(value);
+      } else {
+        writer.u8(0xcf);
+        writer.i64(value);
+      }
+    } else if (value >= -0x20) {
+      writer.u8(value & 0xff);
+    } else {
+      writer.u8(0xd3);
+      writer.i64(value);
+    }
+  } else if (typeof value === 'number') {
+    writer.u8(0xcb);
+    writer.f64(value);
+  } else if (typeof value === 'string') {
+    const bytes = textEncoder.encode(value);
+    if (bytes.length < 32) {
+      writer.u8(0xa0 | bytes.length);
+    } else if (bytes.length <= 0xff) {
+      writer.u8(0xd9);
+      writer.u8(bytes.length);
+    } else if (bytes.length <= 0xffff) {
+      writer.u8(0xda);
+      writer.u16(bytes.length);
+    } else {
+      writer.u8(0xdb);
+      writer.u32(bytes.length);
+    }
+    writer.raw(bytes);
+  } else if (Array.isArray(value) || ArrayBuffer.isView(value)) {
+    const items = Array.from(value as ArrayLike<any>);
+    if (items.length < 16) {
+      writer.u8(0x90 | items.length);
+    } else if (items.length <= 0xffff) {
+      writer.u8(0xdc);
+      writer.u16(items.length);
+    } else {
+      writer.u8(0xdd);
+      writer.u32(items.length);
+    }
+    items.forEach(item => encodeMsgpack(writer, item));
+  } else if (typeof value === 'object') {
+    const fields = entries(value);
+    if (fields.length < 16) {
+      writer.u8(0x80 | fields.length);
+    } else if (fields.length <= 0xffff) {
+      writer.u8(0xde);
+      writer.u16(fields.length);
+    } else {
+      writer.u8(0xdf);
+      writer.u32(fields.length);
+    }
+    fields.forEach(([key, field]) => {
+      encodeMsgpack(writer, key);
+      encodeMsgpack(writer, field);
+    });
+  } else {
+    throw new Error(`can't encode a ${typeof value} as MessagePack`);
+  }
+};
+
+const decodeMsgpack = (reader: Reader): any => {
+  const array = (length: number): any[] => Array.from({ length }, () => decodeMsgpack(reader));
+  const map = (length: number): Record<string, any> => {
+    const object: Record<string, any> = {};
+    for (let i = 0; i < length; i++) {
+      const key = decodeMsgpack(reader);
+      object[String(key)] = decodeMsgpack(reader);
+    }
+    return object;
+  };
+  const str = (length: number): string => textDecoder.decode(reader.raw(length));
+  const bin = (length: number): number[] => Array.from(reader.raw(length));
+
+  const byte = reader.u8();
+  if (byte < 0x80) {
+    return byte;
+  } else if (byte < 0x90) {
+    return map(byte & 0x0f);
+  } else if (byte < 0xa0) {
+    return array(byte & 0x0f);
+  } else if (byte < 0xc0) {
+    return str(byte & 0x1f);
+  } else if (byte >= 0xe0) {
+    return byte - 0x100;
+  }
+  switch (byte) {
+    case 0xc0:
+      return null;
+    case 0xc2:
+      return false;
+    case 0xc3:
+      return true;
+    case 0xc4:
+      return bin(reader.u8());
+    case 0xc5:
+      return bin(reader.u16());
+    case 0xc6:
+      return bin(reader.u32());
+    case 0xca:
+      return reader.f32();
+    case 0xcb:
+      return reader.f64();
+    case 0xcc:
+      return reader.u8();
+    case 0xcd:
+      return reader.u16();
+    case 0xce:
+      return reader.u32();
+    case 0xcf:
+      return reader.u64();
+    case 0xd0:
+      return reader.i8();
+    case 0xd1:
+      return reader.i16();
+    case 0xd2:
+      return reader.i32();
+    case 0xd3:
+      return reader.i64();
+    case 0xd9:
+      return str(reader.u8());
+    case 0xda:
+      return str(reader.u16());
+    case 0xdb:
+      return str(reader.u32());
+    case 0xdc:
+      return array(reader.u16());
+    case 0xdd:
+      return array(reader.u32());
+    case 0xde:
+      return map(reader.u16());
+    case 0xdf:
+      return map(reader.u32());
+    default:
+      throw new Error(`unsupported MessagePack type 0x${byte.toString(16)}`);
+  }
+};
+
+// The initial byte and argument of a CBOR data item.
+const cborHead = (writer: Writer, major: number, argument: number): void => {
+  if (argument < 24) {
+    writer.u8((major << 5) | argument);
+  } else if (argument <= 0xff) {
+    writer.u8((major << 5) | 24);
+    writer.u8(argument);
+  } else if (argument <= 0xffff) {
+    writer.u8((major << 5) | 25);
+    writer.u16(argument);
+  } else if (argument <= 0xffffffff) {
+    writer.u8((major << 5) | 26);
+    writer.u32(argument);
+  } else {
+    writer.u8((major << 5) | 27);
+    writer.i64(argument);
+  }
+};
+
+const encodeCbor = (writer: Writer, value: any): void => {
+  value = toNumber(toJSON(value));
+  if (value === null || value === undefined) {
+    writer.u8(0xf6);
+  } else if (typeof value === 'boolean') {
+    writer.u8(value ? 0xf5 : 0xf4);
+  } else if (Number.isSafeInteger(value)) {
+    if (value >= 0) {
+      cborHead(writer, 0, value);
+    } else {
+      cborHead(writer, 1, -1 - value);
+    }
+  } else if (typeof value === 'number') {
+    writer.u8(0xfb);
+    writer.f64(value);
+  } else if (typeof value === 'string') {
+    const bytes = textEncoder.encode(value);
+    cborHead(writer, 3, bytes.length);
+    writer.raw(bytes);
+  } else if (Array.isArray(value) || ArrayBuffer.isView(value)) {
+    const items = Array.from(value as ArrayLike<any>);
+    cborHead(writer, 4, items.length);
+    items.forEach(item => encodeCbor(writer, item));
+  } else if (typeof value === 'object') {
+    const fields = entries(value);
+    cborHead(writer, 5, fields.length);
+    fields.forEach(([key, field]) => {
+      encodeCbor(writer, key);
+      encodeCbor(writer, field);
+    });
+  } else {
+    throw new Error(`can't encode a ${typeof value} as CBOR`);
+  }
+};
+
+// `undefined` for the indefinite length of the additional information 31
+const cborArgument = (reader: Reader, info: number): number | undefined => {
+  if (info < 24) {
+    return info;
+  }
+  switch (info) {
+    case 24:
+      return reader.u8();
+    case 25:
+      return reader.u16();
+    case 26:
+      return reader.u32();
+    case 27:
+      return reader.u64();
+    case 31:
+      return undefined;
+    default:
+      throw new Error(`unsupported CBOR additional information ${info}`);
+  }
+};
+
+const BREAK = 0xff;
+
+const decodeCbor = (reader: Reader): any => {
+  const byte = reader.u8();
+  const major = byte >> 5;
+  const info = byte & 0x1f;
+  if (major === 7) {
+    switch (info) {
+      case 20:
+        return false;
+      case 21:
+        return true;
+      case 22:
+      case 23:
+        return null;
+      case 25:
+        return reader.f16();
+      case 26:
+        return reader.f32();
+      case 27:
+        return reader.f64();
+      default:
+        throw new Error(`unsupported CBOR simple value ${info}`);
+    }
+  }
+
+  const length = cborArgument(reader, info);
+  // items until the break byte if the length is indefinite
+  const items = (decode: () => void): void => {
+    if (length === undefined) {
+      while (reader.peek() !== BREAK) {
+        decode();
+      }
+      reader.u8();
+    } else {
+      for (let i = 0; i < length; i++) {
+        decode();
+      }
+    }
+  };
+  switch (major) {
+    case 0:
+      return length;
+    case 1:
+      return -1 - length!;
+    case 2:
+    case 3: {
+      const chunks: Uint8Array[] = [];
+      if (length === undefined) {
+        // definite length chunks of the same major type
+        items(() => chunks.push(reader.raw(cborArgument(reader, reader.u8() & 0x1f)!)));
+      } else {
+        chunks.push(reader.raw(length));
+      }
+      const bytes = new Uint8Array(chunks.reduce((sum, chunk) => sum + chunk.length, 0));
+      let offset = 0;
+      for (const chunk of chunks) {
+        bytes.set(chunk, offset);
+        offset += chunk.length;
+      }
+      return major === 2 ? Array.from(bytes) : textDecoder.decode(bytes);
+    }
+    case 4: {
+      const array: any[] = [];
+      items(() => array.push(decodeCbor(reader)));
+      return array;
+    }
+    case 5: {
+      const object: Record<string, any> = {};
+      items(() => {
+        const key = decodeCbor(reader);
+        object[String(key)] = decodeCbor(reader);
+      });
+      return object;
+    }
+    default:
+      // tags, e.g. for dates, are decoded as the tagged value
+      return decodeCbor(reader);
+  }
+};
+
+const decodeWith = (decode: (reader: Reader) => any) => (bytes: Uint8Array): any => {
+  const reader = new Reader(bytes);
+  const value = decode(reader);
+  reader.end();
+  return value;
+};
+
+export const msgpack: __request.Encoding = {
+  mediaType: 'application/msgpack',
+  encode: (value: any): Uint8Array => {
+    const writer = new Writer();
+    encodeMsgpack(writer, value);
+    return writer.finish();
+  },
+  decode: decodeWith(decodeMsgpack),
+};
+
+export const cbor: __request.Encoding = {
+  mediaType: 'application/cbor',
+  encode: (value: any): Uint8Array => {
+    const writer = new Writer();
+    encodeCbor(writer, value);
+    return writer.finish();
+  },
+  decode: decodeWith(decodeCbor),
+};
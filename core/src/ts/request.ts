@@ -1,12 +1,33 @@
 export type ServerConfig = {
   url: string;
+  /**
+   * Encoding of arguments and responses instead of JSON, e.g. the exported `msgpack` or `cbor`.
+   * The server needs the feature of the same name.
+   */
+  encoding?: Encoding;
+};
+
+/**
+ * A binary encoding of procedure arguments and responses.
+ */
+export type Encoding = {
+  readonly mediaType: string;
+  readonly encode: (value: any) => Uint8Array;
+  readonly decode: (bytes: Uint8Array) => any;
 };
 
 export type RequestOptions = {
   readonly method: 'GET' | 'PUT' | 'POST' | 'DELETE' | 'OPTIONS' | 'HEAD' | 'PATCH';
   readonly url: string;
   readonly path?: Record<string, any>;
+  /**
+   * Sent in a `Cookie` header outside of browsers, which drop it and send the cookies they store.
+   */
   readonly cookies?: Record<string, any>;
+  /**
+   * Sends the cookies the browser stores for the server, also to other origins.
+   */
+  readonly withCredentials?: boolean;
   readonly headers?: Record<string, any>;
   readonly query?: Record<string, any>;
   readonly formData?: Record<string, any>;
@@ -14,6 +35,14 @@ export type RequestOptions = {
   readonly mediaType?: string;
   readonly responseHeader?: string;
   readonly errors?: Record<number, string>;
+  readonly validateArguments?: () => void;
+  readonly validate?: (body: any) => any;
+  /**
+   * Wraps a file received as the response, e.g. as the `Ok` of a `Result`.
+   */
+  readonly file?: (file: Blob) => any;
+  readonly revive?: (body: any) => any;
+  readonly traceparent?: boolean;
 };
 
 export class ApiError extends Error {
@@ -35,6 +64,51 @@ export class ApiError extends Error {
   }
 }
 
+/**
+ * Thrown for `401 Unauthorized` responses, e.g. when the context of the request couldn't be resolved.
+ */
+export class UnauthorizedError extends ApiError {
+  constructor(request: RequestOptions, response: ApiResult) {
+    super(request, response, response.body?.message ?? 'Unauthorized');
+    this.name = 'UnauthorizedError';
+  }
+}
+
+/**
+ * Thrown for `403 Forbidden` responses, e.g. when a guard of the procedure rejected the request.
+ */
+export class ForbiddenError extends ApiError {
+  constructor(request: RequestOptions, response: ApiResult) {
+    super(request, response, response.body?.message ?? 'Forbidden');
+    this.name = 'ForbiddenError';
+  }
+}
+
+/**
+ * An argument breaking a constraint set with `#[validate(..)]`.
+ */
+export type FieldError = {
+  /** Name of the parameter, missing for functions not using `#[ts_export]`. */
+  readonly parameter?: string;
+  /** Field names and indices in the argument separated by dots, e.g. `addresses.0.zip`. */
+  readonly path: string;
+  readonly code: 'email' | 'url' | 'length' | 'range';
+  readonly message: string;
+};
+
+/**
+ * Thrown for `422 Unprocessable Entity` responses when arguments break their constraints.
+ */
+export class InvalidArgumentsError extends ApiError {
+  public readonly errors: FieldError[];
+
+  constructor(request: RequestOptions, response: ApiResult) {
+    super(request, response, response.body?.message ?? 'Invalid arguments');
+    this.name = 'InvalidArgumentsError';
+    this.errors = response.body?.errors ?? [];
+  }
+}
+
 export type ApiResult = {
   readonly url: string;
   readonly ok: boolean;
@@ -72,6 +146,9 @@ const isFormData = (value: any): value is FormData => {
   return value instanceof FormData;
 };
 
+// @ts-ignore
+const isBrowser = typeof window !== 'undefined' && typeof document !== 'undefined';
+
 const base64 = (str: string): string => {
   try {
     return btoa(str);
@@ -169,9 +246,74 @@ const resolve = async <T>(
   return resolver;
 };
 
+let traceparentProvider: (() => string | undefined) | undefined;
+
+/**
+ * Sets the function providing the `traceparent` header, e.g. from the active span of a browser
+ * tracing library. Without one, or if it returns `undefined`, a random trace is started per request.
+ */
+export const setTraceparentProvider = (provider: (() => string | undefined) | undefined): void => {
+  traceparentProvider = provider;
+};
+
+const randomHex = (bytes: number): string => {
+  const values = new Uint8Array(bytes);
+  if (typeof crypto !== 'undefined' && crypto.getRandomValues) {
+    crypto.getRandomValues(values);
+  } else {
+    for (let i = 0; i < bytes; i++) {
+      values[i] = Math.floor(Math.random() * 256);
+    }
+  }
+  return Array.from(values, (b) => b.toString(16).padStart(2, '0')).join('');
+};
+
+const getTraceparent = (): string => {
+  return traceparentProvider?.() ?? `00-${randomHex(16)}-${randomHex(8)}-01`;
+};
+
+// The config of the `createClient` whose procedure is starting a request.
+let boundConfig: ServerConfig | undefined;
+
+/**
+ * Wraps the functions in `procedures`, including the ones of nested namespaces, so the requests
+ * they start use `config`. Used by `createClient`.
+ */
+export const bindConfig = <T>(procedures: T, config: ServerConfig): T => {
+  if (typeof procedures === 'function') {
+    const procedure = procedures as unknown as (...args: any[]) => any;
+    const bound = (...args: any[]) => {
+      const previous = boundConfig;
+      boundConfig = config;
+      try {
+        return procedure(...args);
+      } finally {
+        boundConfig = previous;
+      }
+    };
+    // the functions of its namespace, e.g. `withMeta`
+    return Object.assign(bound, bindConfig({ ...procedures }, config)) as unknown as T;
+  }
+  if (typeof procedures === 'object' && procedures !== null) {
+    return Object.entries(procedures)
+      .filter(([key]) => !key.startsWith('__'))
+      .reduce(
+        (bound, [key, value]) => ({ ...bound, [key]: bindConfig(value, config) }),
+        {} as Record<string, any>
+      ) as T;
+  }
+  return procedures;
+};
+
+// Procedures send json, which is replaced by the encoding of the config.
+const getEncoding = (config: ServerConfig, options: RequestOptions): Encoding | undefined => {
+  return options.mediaType === 'application/json' ? config.encoding : undefined;
+};
+
 const getHeaders = async (config: ServerConfig, options: RequestOptions): Promise<Headers> => {
+  const encoding = getEncoding(config, options);
   const headers = Object.entries({
-    Accept: 'application/json',
+    Accept: encoding ? `${encoding.mediaType}, application/json` : 'application/json',
     ...options.headers,
   })
     .filter(([_, value]) => isDefined(value))
@@ -183,8 +325,22 @@ const getHeaders = async (config: ServerConfig, options: RequestOptions): Promis
       {} as Record<string, string>
     );
 
+  if (options.traceparent && !Object.keys(headers).some((key) => key.toLowerCase() === 'traceparent')) {
+    headers['traceparent'] = getTraceparent();
+  }
+
+  // Browsers drop this header, they send the cookies they store with `withCredentials`.
+  const cookies = Object.entries(isBrowser ? {} : options.cookies ?? {})
+    .filter(([_, value]) => isDefined(value))
+    .map(([key, value]) => `${key}=${encodeURIComponent(String(value))}`);
+  if (cookies.length > 0) {
+    headers['Cookie'] = cookies.join('; ');
+  }
+
   if (options.body) {
-    if (options.mediaType) {
+    if (encoding) {
+      headers['Content-Type'] = encoding.mediaType;
+    } else if (options.mediaType) {
       headers['Content-Type'] = options.mediaType;
     } else if (isBlob(options.body)) {
       headers['Content-Type'] = options.body.type || 'application/octet-stream';
@@ -198,9 +354,12 @@ const getHeaders = async (config: ServerConfig, options: RequestOptions): Promis
   return new Headers(headers);
 };
 
-const getRequestBody = (options: RequestOptions): any => {
+const getRequestBody = (config: ServerConfig, options: RequestOptions): any => {
   if (options.body) {
-    if (options.mediaType?.includes('/json')) {
+    const encoding = getEncoding(config, options);
+    if (encoding) {
+      return encoding.encode(options.body);
+    } else if (options.mediaType?.includes('/json')) {
       return JSON.stringify(options.body);
     } else if (isString(options.body) || isBlob(options.body) || isFormData(options.body)) {
       return options.body;
@@ -227,6 +386,7 @@ export const sendRequest = async (
     body: body ?? formData,
     method: options.method,
     signal: controller.signal,
+    credentials: options.withCredentials ? 'include' : undefined,
   };
 
   onCancel(() => controller.abort());
@@ -244,16 +404,38 @@ const getResponseHeader = (response: Response, responseHeader?: string): string
   return undefined;
 };
 
-const getResponseBody = async (response: Response): Promise<any> => {
+// The file name of an `attachment` response, `''` if it has none and `undefined` for other responses.
+const getAttachmentName = (contentDisposition: string | null): string | undefined => {
+  if (!contentDisposition?.toLowerCase().startsWith('attachment')) {
+    return undefined;
+  }
+  const encoded = /filename\*=UTF-8''([^;]+)/i.exec(contentDisposition);
+  if (encoded) {
+    return decodeURIComponent(encoded[1]);
+  }
+  const quoted = /filename="((?:[^"\\]|\\.)*)"/i.exec(contentDisposition);
+  return quoted ? quoted[1].replace(/\\(.)/g, '$1') : '';
+};
+
+const getResponseBody = async (response: Response, encoding?: Encoding): Promise<any> => {
   if (response.status !== 204) {
     try {
       const contentType = response.headers.get('Content-Type');
-      if (contentType) {
+      const attachmentName = getAttachmentName(response.headers.get('Content-Disposition'));
+      if (attachmentName !== undefined) {
+        const blob = await response.blob();
+        return attachmentName ? new File([blob], attachmentName, { type: blob.type }) : blob;
+      } else if (contentType) {
         const isJSON = contentType.toLowerCase().startsWith('application/json');
-        if (isJSON) {
+        const isText = contentType.toLowerCase().startsWith('text/');
+        if (encoding && contentType.toLowerCase().startsWith(encoding.mediaType)) {
+          return encoding.decode(new Uint8Array(await response.arrayBuffer()));
+        } else if (isJSON) {
           return await response.json();
-        } else {
+        } else if (isText) {
           return await response.text();
+        } else {
+          return await response.blob();
         }
       }
     } catch (error) {
@@ -264,6 +446,16 @@ const getResponseBody = async (response: Response): Promise<any> => {
 };
 
 const catchErrorCodes = (options: RequestOptions, result: ApiResult): void => {
+  if (result.status === 401) {
+    throw new UnauthorizedError(options, result);
+  }
+  if (result.status === 403) {
+    throw new ForbiddenError(options, result);
+  }
+  if (result.status === 422 && result.body?.kind === 'validation') {
+    throw new InvalidArgumentsError(options, result);
+  }
+
   const errors: Record<number, string> = {
     400: 'Bad Request',
     401: 'Unauthorized',
@@ -285,6 +477,33 @@ const catchErrorCodes = (options: RequestOptions, result: ApiResult): void => {
   }
 };
 
+/**
+ * What a procedure resolves to and the headers of the response, with lowercase names.
+ * The headers declared by the procedure are typed.
+ */
+export type WithMeta<T, H extends string = never> = {
+  readonly data: T;
+  readonly headers: Partial<Record<H, string>> & Record<string, string | undefined>;
+};
+
+// Whether the procedure starting a request is called through its `withMeta` variant.
+let metaRequested = false;
+
+/**
+ * Calls `procedure`, resolving to `{ data, headers }` instead of the data.
+ * Used by the `withMeta` variants of procedures.
+ */
+export const withMeta = <T, H extends string>(
+  procedure: () => CancelablePromise<T>
+): CancelablePromise<WithMeta<T, H>> => {
+  metaRequested = true;
+  try {
+    return procedure() as unknown as CancelablePromise<WithMeta<T, H>>;
+  } finally {
+    metaRequested = false;
+  }
+};
+
 /**
  * Request method
  * @param config The OpenAPI configuration object
@@ -293,16 +512,20 @@ const catchErrorCodes = (options: RequestOptions, result: ApiResult): void => {
  * @throws ApiError
  */
 export const request = <T>(config: ServerConfig, options: RequestOptions): CancelablePromise<T> => {
+  // read before the first `await`, while the procedure of a `createClient` or `withMeta` is running
+  config = boundConfig ?? config;
+  const meta = metaRequested;
   return new CancelablePromise(async (resolve, reject, onCancel) => {
     try {
+      options.validateArguments?.();
       const url = getUrl(config, options);
       const formData = getFormData(options);
-      const body = getRequestBody(options);
+      const body = getRequestBody(config, options);
       const headers = await getHeaders(config, options);
 
       if (!onCancel.isCancelled) {
         const response = await sendRequest(config, options, url, body, formData, headers, onCancel);
-        const responseBody = await getResponseBody(response);
+        const responseBody = await getResponseBody(response, getEncoding(config, options));
         const responseHeader = getResponseHeader(response, options.responseHeader);
 
         const result: ApiResult = {
@@ -315,7 +538,16 @@ export const request = <T>(config: ServerConfig, options: RequestOptions): Cance
 
         catchErrorCodes(options, result);
 
-        resolve(result.body);
+        const body = options.file && isBlob(result.body) ? options.file(result.body) : result.body;
+        const validated = options.validate ? options.validate(body) : body;
+        const data = options.revive ? options.revive(validated) : validated;
+        if (meta) {
+          const headers: Record<string, string> = {};
+          response.headers.forEach((value, key) => (headers[key] = value));
+          resolve({ data, headers } as T);
+        } else {
+          resolve(data);
+        }
       }
     } catch (error) {
       reject(error);
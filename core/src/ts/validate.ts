@@ -0,0 +1,286 @@
+export type Issue = {
+  readonly path: string;
+  readonly expected: string;
+  readonly received: any;
+  /** Set when the value breaks a constraint of its field, e.g. `must be an email address`. */
+  readonly message?: string;
+};
+
+/**
+ * A schema checks a value and returns the first issue it finds, or `undefined` if the value is valid.
+ */
+export type Schema = (value: any, path: string) => Issue | undefined;
+
+const describe = (value: any): string => {
+  if (value === null) {
+    return 'null';
+  }
+  if (Array.isArray(value)) {
+    return 'array';
+  }
+  return typeof value;
+};
+
+export class ValidationError extends Error {
+  public readonly path: string;
+  public readonly expected: string;
+  public readonly received: any;
+
+  constructor(message: string, issue: Issue) {
+    super(
+      issue.message
+        ? `${message}: \`${issue.path}\` ${issue.message}`
+        : `${message}: expected ${issue.expected} at \`${issue.path}\`, received ${describe(issue.received)}`
+    );
+
+    this.name = 'ValidationError';
+    this.path = issue.path;
+    this.expected = issue.expected;
+    this.received = issue.received;
+  }
+}
+
+export class ResponseValidationError extends ValidationError {
+  public readonly procedure: string;
+
+  constructor(procedure: string, issue: Issue) {
+    super(`Invalid response from \`${procedure}\``, issue);
+
+    this.name = 'ResponseValidationError';
+    this.procedure = procedure;
+  }
+}
+
+export class ArgumentValidationError extends ValidationError {
+  public readonly procedure: string;
+
+  constructor(procedure: string, issue: Issue) {
+    super(`Invalid argument for \`${procedure}\``, issue);
+
+    this.name = 'ArgumentValidationError';
+    this.procedure = procedure;
+  }
+}
+
+export const any: Schema = () => undefined;
+
+export const never: Schema = (value, path) => ({ path, expected: 'never', received: value });
+
+const primitive =
+  (expected: string): Schema =>
+  (value, path) =>
+    typeof value === expected ? undefined : { path, expected, received: value };
+
+export const string = primitive('string');
+export const number = primitive('number');
+export const boolean = primitive('boolean');
+
+export const bigint: Schema = (value, path) =>
+  typeof value === 'number' || typeof value === 'bigint'
+    ? undefined
+    : { path, expected: 'bigint', received: value };
+
+export const literal =
+  (expected: any): Schema =>
+  (value, path) =>
+    value === expected ? undefined : { path, expected: JSON.stringify(expected), received: value };
+
+export const array =
+  (item: Schema): Schema =>
+  (value, path) => {
+    if (!Array.isArray(value)) {
+      return { path, expected: 'array', received: value };
+    }
+    for (let i = 0; i < value.length; i++) {
+      const issue = item(value[i], `${path}[${i}]`);
+      if (issue) {
+        return issue;
+      }
+    }
+    return undefined;
+  };
+
+export const tuple =
+  (items: Schema[]): Schema =>
+  (value, path) => {
+    if (!Array.isArray(value) || value.length !== items.length) {
+      return { path, expected: `tuple of length ${items.length}`, received: value };
+    }
+    for (let i = 0; i < items.length; i++) {
+      const issue = items[i](value[i], `${path}[${i}]`);
+      if (issue) {
+        return issue;
+      }
+    }
+    return undefined;
+  };
+
+export const object =
+  (fields: Record<string, [Schema, boolean]>): Schema =>
+  (value, path) => {
+    if (typeof value !== 'object' || value === null || Array.isArray(value)) {
+      return { path, expected: 'object', received: value };
+    }
+    for (const [key, [schema, optional]] of Object.entries(fields)) {
+      if (optional && value[key] === undefined) {
+        continue;
+      }
+      const issue = schema(value[key], `${path}.${key}`);
+      if (issue) {
+        return issue;
+      }
+    }
+    return undefined;
+  };
+
+export const record =
+  (item: Schema): Schema =>
+  (value, path) => {
+    if (typeof value !== 'object' || value === null || Array.isArray(value)) {
+      return { path, expected: 'object', received: value };
+    }
+    for (const [key, v] of Object.entries(value)) {
+      const issue = item(v, `${path}.${key}`);
+      if (issue) {
+        return issue;
+      }
+    }
+    return undefined;
+  };
+
+export const union =
+  (variants: Schema[]): Schema =>
+  (value, path) => {
+    // Report the most specific issue, which usually belongs to the variant that was meant.
+    let best: Issue | undefined;
+    for (const variant of variants) {
+      const issue = variant(value, path);
+      if (!issue) {
+        return undefined;
+      }
+      if (!best || issue.path.length > best.path.length) {
+        best = issue;
+      }
+    }
+    return best ?? { path, expected: 'never', received: value };
+  };
+
+export const intersection =
+  (parts: Schema[]): Schema =>
+  (value, path) => {
+    for (const part of parts) {
+      const issue = part(value, path);
+      if (issue) {
+        return issue;
+      }
+    }
+    return undefined;
+  };
+
+/**
+ * Defers looking up a schema, so declarations can reference each other in any order.
+ */
+export const lazy =
+  (get: () => Schema): Schema =>
+  (value, path) =>
+    get()(value, path);
+
+/**
+ * Constraints of a field set with `#[validate(..)]`, named like the attributes of `<input>`.
+ */
+export type Constraints = {
+  readonly email?: boolean;
+  readonly url?: boolean;
+  readonly minLength?: number;
+  readonly maxLength?: number;
+  readonly min?: number;
+  readonly max?: number;
+};
+
+const isEmail = (text: string): boolean => {
+  const at = text.indexOf('@');
+  const domain = text.slice(at + 1);
+  return (
+    at > 0 &&
+    !domain.includes('@') &&
+    domain.split('.').length > 1 &&
+    domain.split('.').every((label) => label !== '') &&
+    !/\s/.test(text)
+  );
+};
+
+const isUrl = (text: string): boolean => {
+  const end = text.indexOf('://');
+  const rest = text.slice(end + 3);
+  return (
+    end > 0 &&
+    /^[a-zA-Z][a-zA-Z0-9+.-]*$/.test(text.slice(0, end)) &&
+    rest !== '' &&
+    !rest.startsWith('/') &&
+    !/\s/.test(text)
+  );
+};
+
+const outside = (value: number, min?: number, max?: number): boolean =>
+  (min !== undefined && value < min) || (max !== undefined && value > max);
+
+const bounds = (min?: number, max?: number, of = ''): string =>
+  min !== undefined && max !== undefined
+    ? `between ${min} and ${max}`
+    : min !== undefined
+      ? `${of}at least ${min}`
+      : `${of}at most ${max}`;
+
+/**
+ * The message of the first constraint `value` breaks, the same as the server's, or `undefined`.
+ * `null` and `undefined` break none.
+ */
+export const checkConstraints = (constraints: Constraints, value: any): string | undefined => {
+  const { email, url, minLength, maxLength, min, max } = constraints;
+  if (typeof value === 'string' && email && !isEmail(value)) {
+    return 'must be an email address';
+  }
+  if (typeof value === 'string' && url && !isUrl(value)) {
+    return 'must be a url';
+  }
+  // text is measured in code points, like rust `char`s
+  const length = typeof value === 'string' ? [...value].length : Array.isArray(value) ? value.length : undefined;
+  if (length !== undefined && outside(length, minLength, maxLength)) {
+    return `must have a length ${bounds(minLength, maxLength, 'of ')}`;
+  }
+  if ((typeof value === 'number' || typeof value === 'bigint') && outside(Number(value), min, max)) {
+    return `must be ${bounds(min, max)}`;
+  }
+  return undefined;
+};
+
+/**
+ * Checks the constraints of a field after its type.
+ */
+export const constrained =
+  (schema: Schema, constraints: Constraints): Schema =>
+  (value, path) => {
+    const issue = schema(value, path);
+    if (issue) {
+      return issue;
+    }
+    const message = checkConstraints(constraints, value);
+    return message ? { path, expected: 'a valid value', received: value, message } : undefined;
+  };
+
+export const assertArguments = (procedure: string, params: [string, Schema][], args: any[]): void => {
+  params.forEach(([name, schema], i) => {
+    const issue = schema(args[i], name);
+    if (issue) {
+      throw new ArgumentValidationError(procedure, issue);
+    }
+  });
+};
+
+export const assertResponse = (procedure: string, schema: Schema, value: any): any => {
+  const issue = schema(value, '$');
+  if (issue) {
+    throw new ResponseValidationError(procedure, issue);
+  }
+  return value;
+};
@@ -0,0 +1,663 @@
+//! A small parser for the typescript declarations produced by ts-rs.
+//!
+//! `TsFn` only carries declarations as strings. Everything that needs to know the shape of a
+//! type (runtime validators, manifests, ...) parses them with this module instead.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TsType {
+    /// `string`, `number`, `boolean`, `null`, `any`, ...
+    Keyword(String),
+    /// A literal type exactly as written, e.g. `"Ok"`, `1` or `true`.
+    Literal(String),
+    Array(Box<TsType>),
+    Tuple(Vec<TsType>),
+    Object(Vec<TsField>),
+    /// `{ [key: K]: V }`
+    Index(Box<TsType>, Box<TsType>),
+    Union(Vec<TsType>),
+    Intersection(Vec<TsType>),
+    /// A named type, possibly with generics, e.g. `Array<string>` or `Password`.
+    Reference(String, Vec<TsType>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TsField {
+    pub name: String,
+    pub optional: bool,
+    pub ty: TsType,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub docs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TsDeclaration {
+    pub name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub generics: Vec<String>,
+    pub ty: TsType,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub docs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to parse typescript type: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse_type(s: &str) -> Result<TsType, ParseError> {
+    let mut parser = Parser::new(s)?;
+    let ty = parser.ty()?;
+    parser.expect_end()?;
+    Ok(ty)
+}
+
+/// Parses `type Name<T> = ...;` or `interface Name<T> { ... }`, optionally preceded by `export`.
+pub fn parse_declaration(s: &str) -> Result<TsDeclaration, ParseError> {
+    let mut parser = Parser::new(s)?;
+    let docs = parser.docs();
+    parser.eat_ident("export");
+    let is_interface = if parser.eat_ident("interface") {
+        true
+    } else if parser.eat_ident("type") {
+        false
+    } else {
+        return Err(parser.error("expected `type` or `interface`"));
+    };
+    let name = parser.ident()?;
+    let mut generics = Vec::new();
+    if parser.eat(&Token::Punct('<')) {
+        loop {
+            generics.push(parser.ident()?);
+            // Bounds and defaults don't matter for us.
+            while !matches!(parser.peek(), Some(Token::Punct(',' | '>')) | None) {
+                parser.next();
+            }
+            if !parser.eat(&Token::Punct(',')) {
+                break;
+            }
+        }
+        parser.expect(&Token::Punct('>'))?;
+    }
+    let ty = if is_interface {
+        if parser.eat_ident("extends") {
+            return Err(parser.error("interfaces with `extends` are not supported"));
+        }
+        parser.object()?
+    } else {
+        parser.expect(&Token::Punct('='))?;
+        parser.ty()?
+    };
+    parser.eat(&Token::Punct(';'));
+    parser.expect_end()?;
+    Ok(TsDeclaration {
+        name,
+        generics,
+        ty,
+        docs,
+    })
+}
+
+impl TsType {
+    /// `T | null`, as ts-rs renders `Option<T>`.
+    pub fn is_nullable(&self) -> bool {
+        match self {
+            TsType::Keyword(k) => k == "null" || k == "undefined" || k == "any" || k == "unknown",
+            TsType::Union(variants) => variants.iter().any(|v| v.is_nullable()),
+            _ => false,
+        }
+    }
+
+    /// Calls `f` for every type reference in `self`, including nested ones.
+    pub fn visit_references(&self, f: &mut impl FnMut(&str, &[TsType])) {
+        match self {
+            TsType::Keyword(_) | TsType::Literal(_) => {}
+            TsType::Array(t) => t.visit_references(f),
+            TsType::Index(k, v) => {
+                k.visit_references(f);
+                v.visit_references(f);
+            }
+            TsType::Tuple(ts) | TsType::Union(ts) | TsType::Intersection(ts) => {
+                ts.iter().for_each(|t| t.visit_references(f))
+            }
+            TsType::Object(fields) => fields.iter().for_each(|field| field.ty.visit_references(f)),
+            TsType::Reference(name, generics) => {
+                f(name, generics);
+                generics.iter().for_each(|t| t.visit_references(f));
+            }
+        }
+    }
+
+    /// Replaces the generic parameters `names` with `args`.
+    pub fn substitute(&self, names: &[String], args: &[TsType]) -> TsType {
+        let sub = |t: &TsType| t.substitute(names, args);
+        match self {
+            TsType::Keyword(_) | TsType::Literal(_) => self.clone(),
+            TsType::Array(t) => TsType::Array(Box::new(sub(t))),
+            TsType::Index(k, v) => TsType::Index(Box::new(sub(k)), Box::new(sub(v))),
+            TsType::Tuple(ts) => TsType::Tuple(ts.iter().map(sub).collect()),
+            TsType::Union(ts) => TsType::Union(ts.iter().map(sub).collect()),
+            TsType::Intersection(ts) => TsType::Intersection(ts.iter().map(sub).collect()),
+            TsType::Object(fields) => TsType::Object(
+                fields
+                    .iter()
+                    .map(|f| TsField {
+                        ty: sub(&f.ty),
+                        ..f.clone()
+                    })
+                    .collect(),
+            ),
+            TsType::Reference(name, generics) if generics.is_empty() => {
+                match names.iter().position(|n| n == name) {
+                    Some(i) if i < args.len() => args[i].clone(),
+                    _ => self.clone(),
+                }
+            }
+            TsType::Reference(name, generics) => {
+                TsType::Reference(name.clone(), generics.iter().map(sub).collect())
+            }
+        }
+    }
+}
+
+/// Renders `docs` as a JSDoc comment, ending with a newline.
+pub fn js_doc(docs: &str, indent: &str) -> String {
+    let docs = docs.replace("*/", "*\\/");
+    let mut lines = docs.lines();
+    match (lines.next(), lines.next()) {
+        (Some(line), None) => format!("{indent}/** {} */\n", line.trim()),
+        _ => {
+            let lines = docs
+                .lines()
+                .map(|l| format!("{indent} * {l}").trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{indent}/**\n{lines}\n{indent} */\n")
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, types: &[TsType], sep: &str) -> fmt::Result {
+    for (i, t) in types.iter().enumerate() {
+        if i > 0 {
+            f.write_str(sep)?;
+        }
+        // Unions inside intersections and arrays need parentheses.
+        match t {
+            TsType::Union(_) if sep != " | " => write!(f, "({t})")?,
+            _ => write!(f, "{t}")?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for TsType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TsType::Keyword(k) => f.write_str(k),
+            TsType::Literal(l) => f.write_str(l),
+            TsType::Array(t) => write!(f, "Array<{t}>"),
+            TsType::Tuple(ts) => {
+                f.write_str("[")?;
+                write_list(f, ts, ", ")?;
+                f.write_str("]")
+            }
+            TsType::Object(fields) => {
+                // Documented fields get a line of their own.
+                let multiline = fields.iter().any(|field| field.docs.is_some());
+                f.write_str(if multiline { "{\n" } else { "{ " })?;
+                for field in fields {
+                    if let Some(docs) = &field.docs {
+                        f.write_str(&js_doc(docs, "  "))?;
+                    }
+                    write!(
+                        f,
+                        "{}{}{}: {},{}",
+                        if multiline { "  " } else { "" },
+                        field_name(&field.name),
+                        if field.optional { "?" } else { "" },
+                        field.ty,
+                        if multiline { "\n" } else { " " },
+                    )?;
+                }
+                f.write_str("}")
+            }
+            TsType::Index(k, v) => write!(f, "{{ [key: {k}]: {v} }}"),
+            TsType::Union(ts) => write_list(f, ts, " | "),
+            TsType::Intersection(ts) => write_list(f, ts, " & "),
+            TsType::Reference(name, generics) => {
+                f.write_str(name)?;
+                if !generics.is_empty() {
+                    f.write_str("<")?;
+                    write_list(f, generics, ", ")?;
+                    f.write_str(">")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for TsDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(docs) = &self.docs {
+            f.write_str(&js_doc(docs, ""))?;
+        }
+        write!(f, "type {}", self.name)?;
+        if !self.generics.is_empty() {
+            write!(f, "<{}>", self.generics.join(", "))?;
+        }
+        write!(f, " = {};", self.ty)
+    }
+}
+
+/// Quotes `name` if it isn't a valid identifier.
+pub fn field_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_' || c == '$')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(String),
+    Doc(String),
+    Punct(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let start = i + 2;
+            let mut end = start;
+            while end + 1 < chars.len() && !(chars[end] == '*' && chars[end + 1] == '/') {
+                end += 1;
+            }
+            if end + 1 >= chars.len() {
+                return Err(ParseError("unterminated comment".into()));
+            }
+            let comment: String = chars[start..end].iter().collect();
+            if let Some(doc) = comment.strip_prefix('*') {
+                let doc = doc
+                    .lines()
+                    .map(|l| l.trim().trim_start_matches('*').trim())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                tokens.push(Token::Doc(doc.trim().to_string()));
+            }
+            i = end + 2;
+        } else if c == '"' || c == '\'' || c == '`' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(ParseError("unterminated string literal".into()));
+            }
+            i += 1;
+            tokens.push(Token::Str(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || chars[i] == '$'
+                    || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "{}[]()<>,:;|&?=".contains(c) {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        } else {
+            return Err(ParseError(format!("unexpected character `{c}`")));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            tokens: tokenize(s)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError(format!("{msg}, found {:?}", self.peek()))
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        self.eat(&Token::Ident(ident.to_string()))
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {token:?}")))
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("expected end of input")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected identifier"))
+            }
+        }
+    }
+
+    /// Collects all doc comments at the current position, keeping the last one.
+    fn docs(&mut self) -> Option<String> {
+        let mut docs = None;
+        while let Some(Token::Doc(doc)) = self.peek() {
+            docs = Some(doc.clone());
+            self.pos += 1;
+        }
+        docs
+    }
+
+    fn ty(&mut self) -> Result<TsType, ParseError> {
+        self.eat(&Token::Punct('|'));
+        let mut variants = vec![self.intersection()?];
+        while self.eat(&Token::Punct('|')) {
+            variants.push(self.intersection()?);
+        }
+        Ok(if variants.len() == 1 {
+            variants.pop().unwrap()
+        } else {
+            TsType::Union(variants)
+        })
+    }
+
+    fn intersection(&mut self) -> Result<TsType, ParseError> {
+        self.eat(&Token::Punct('&'));
+        let mut parts = vec![self.postfix()?];
+        while self.eat(&Token::Punct('&')) {
+            parts.push(self.postfix()?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            TsType::Intersection(parts)
+        })
+    }
+
+    fn postfix(&mut self) -> Result<TsType, ParseError> {
+        let mut ty = self.primary()?;
+        while self.peek() == Some(&Token::Punct('['))
+            && self.tokens.get(self.pos + 1) == Some(&Token::Punct(']'))
+        {
+            self.pos += 2;
+            ty = TsType::Array(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn primary(&mut self) -> Result<TsType, ParseError> {
+        match self.next() {
+            Some(Token::Punct('(')) => {
+                let ty = self.ty()?;
+                self.expect(&Token::Punct(')'))?;
+                Ok(ty)
+            }
+            Some(Token::Punct('[')) => {
+                let mut items = Vec::new();
+                while !self.eat(&Token::Punct(']')) {
+                    items.push(self.ty()?);
+                    if !self.eat(&Token::Punct(',')) {
+                        self.expect(&Token::Punct(']'))?;
+                        break;
+                    }
+                }
+                Ok(TsType::Tuple(items))
+            }
+            Some(Token::Punct('{')) => {
+                self.pos -= 1;
+                self.object()
+            }
+            Some(Token::Str(s)) | Some(Token::Number(s)) => Ok(TsType::Literal(s)),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" | "false" => Ok(TsType::Literal(ident)),
+                "string" | "number" | "bigint" | "boolean" | "null" | "undefined" | "any"
+                | "unknown" | "never" | "void" | "object" => Ok(TsType::Keyword(ident)),
+                _ => {
+                    let mut generics = Vec::new();
+                    if self.eat(&Token::Punct('<')) {
+                        loop {
+                            generics.push(self.ty()?);
+                            if !self.eat(&Token::Punct(',')) {
+                                break;
+                            }
+                        }
+                        self.expect(&Token::Punct('>'))?;
+                    }
+                    if ident == "Array" && generics.len() == 1 {
+                        Ok(TsType::Array(Box::new(generics.pop().unwrap())))
+                    } else {
+                        Ok(TsType::Reference(ident, generics))
+                    }
+                }
+            },
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected type"))
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<TsType, ParseError> {
+        self.expect(&Token::Punct('{'))?;
+        let mut fields = Vec::new();
+        let mut index = None;
+        loop {
+            let docs = self.docs();
+            if self.eat(&Token::Punct('}')) {
+                break;
+            }
+            if self.eat(&Token::Punct('[')) {
+                self.ident()?;
+                self.expect(&Token::Punct(':'))?;
+                let key = self.ty()?;
+                self.expect(&Token::Punct(']'))?;
+                self.expect(&Token::Punct(':'))?;
+                index = Some((key, self.ty()?));
+            } else {
+                let name = match self.next() {
+                    Some(Token::Ident(name)) => name,
+                    Some(Token::Str(name)) => name[1..name.len() - 1].to_string(),
+                    Some(Token::Number(name)) => name,
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error("expected field name"));
+                    }
+                };
+                let optional = self.eat(&Token::Punct('?'));
+                self.expect(&Token::Punct(':'))?;
+                let ty = self.ty()?;
+                fields.push(TsField {
+                    name,
+                    optional,
+                    ty,
+                    docs,
+                });
+            }
+            if !self.eat(&Token::Punct(',')) && !self.eat(&Token::Punct(';')) {
+                self.expect(&Token::Punct('}'))?;
+                break;
+            }
+        }
+        match index {
+            Some((key, value)) if fields.is_empty() => {
+                Ok(TsType::Index(Box::new(key), Box::new(value)))
+            }
+            Some(_) => {
+                Err(self.error("objects mixing fields and index signatures are not supported"))
+            }
+            None => Ok(TsType::Object(fields)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_type() {
+    let string = || TsType::Keyword("string".into());
+    assert_eq!(parse_type("string").unwrap(), string());
+    assert_eq!(
+        parse_type("Array<string>").unwrap(),
+        TsType::Array(Box::new(string()))
+    );
+    assert_eq!(
+        parse_type("string[]").unwrap(),
+        parse_type("Array<string>").unwrap()
+    );
+    assert_eq!(
+        parse_type("string | null").unwrap(),
+        TsType::Union(vec![string(), TsType::Keyword("null".into())])
+    );
+    assert_eq!(
+        parse_type("[number, Foo<Bar>]").unwrap(),
+        TsType::Tuple(vec![
+            TsType::Keyword("number".into()),
+            TsType::Reference("Foo".into(), vec![TsType::Reference("Bar".into(), vec![])])
+        ])
+    );
+    assert_eq!(
+        parse_type("{ [key: string]: number }").unwrap(),
+        TsType::Index(
+            Box::new(string()),
+            Box::new(TsType::Keyword("number".into()))
+        )
+    );
+    assert!(parse_type("Foo<").is_err());
+}
+
+#[test]
+fn test_parse_declaration() {
+    let decl = parse_declaration("interface Password { password: string, hint?: string | null, }")
+        .unwrap();
+    assert_eq!(decl.name, "Password");
+    assert_eq!(
+        decl.to_string(),
+        "type Password = { password: string, hint?: string | null, };"
+    );
+
+    let decl = parse_declaration(r#"type Result<T, E> = { "Ok": T } | { "Err": E };"#).unwrap();
+    assert_eq!(decl.generics, vec!["T", "E"]);
+    assert_eq!(
+        decl.to_string(),
+        "type Result<T, E> = { Ok: T, } | { Err: E, };"
+    );
+
+    let decl = parse_declaration(r#"export type Kind = "A" | "B";"#).unwrap();
+    assert_eq!(
+        decl.ty,
+        TsType::Union(vec![
+            TsType::Literal("\"A\"".into()),
+            TsType::Literal("\"B\"".into())
+        ])
+    );
+
+    let decl = parse_declaration("/** A user. */\ntype User = { /** The name. */ name: string, };")
+        .unwrap();
+    assert_eq!(decl.docs.as_deref(), Some("A user."));
+    match &decl.ty {
+        TsType::Object(fields) => assert_eq!(fields[0].docs.as_deref(), Some("The name.")),
+        _ => panic!("expected object"),
+    }
+    assert_eq!(
+        decl.to_string(),
+        "/** A user. */\ntype User = {\n  /** The name. */\n  name: string,\n};"
+    );
+}
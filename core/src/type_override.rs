@@ -0,0 +1,397 @@
+//! Typescript types of rust types set on the [`Api`](crate::Api) instead of their `TS` impls,
+//! see [`TypeOverride`], and the converters of their values in the client, see `ts/convert.ts`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ts_type::{field_name, parse_declaration, parse_type, TsDeclaration, TsType};
+
+/// How a rust type is typed in the typescript client, see
+/// [`Api::override_type`](crate::Api::override_type).
+///
+/// Values can be converted by the client, e.g. to revive dates as `Date` objects:
+///
+/// ```ignore
+/// let api = Api::new().override_type::<Timestamp>(
+///     TypeOverride::new("Date")
+///         .revive("(value: string) => new Date(value)")
+///         .serialize("(value: Date) => value.toISOString()"),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeOverride {
+    /// The typescript type, `None` keeps the type of the `TS` impl.
+    pub ty: Option<String>,
+    /// Whether the type is branded with its name, see [`TypeOverride::branded`].
+    pub branded: bool,
+    /// A typescript function converting the parsed values of responses.
+    pub revive: Option<String>,
+    /// A typescript function converting arguments before they are sent.
+    pub serialize: Option<String>,
+}
+
+impl TypeOverride {
+    pub fn new(ty: impl Into<String>) -> Self {
+        Self {
+            ty: Some(ty.into()),
+            branded: false,
+            revive: None,
+            serialize: None,
+        }
+    }
+
+    /// Keeps the type of the `TS` impl, intersected with `{ __brand: "<name>" }`, so a `UserId`
+    /// can't be passed where an `OrderId` is expected even though both are strings.
+    /// Values are cast to the brand, e.g. `id as UserId`.
+    pub fn branded() -> Self {
+        Self {
+            ty: None,
+            branded: true,
+            revive: None,
+            serialize: None,
+        }
+    }
+
+    /// Converts values of the type in responses after they are parsed and validated,
+    /// `revive` is a typescript function like `(value: string) => new Date(value)`.
+    /// It isn't called for `null` and `undefined`.
+    pub fn revive(mut self, revive: impl Into<String>) -> Self {
+        self.revive = Some(revive.into());
+        self
+    }
+
+    /// Converts values of the type in arguments before they are validated and sent,
+    /// `serialize` is a typescript function like `(value: Date) => value.toISOString()`.
+    /// It isn't called for `null` and `undefined`.
+    pub fn serialize(mut self, serialize: impl Into<String>) -> Self {
+        self.serialize = Some(serialize.into());
+        self
+    }
+
+    /// The declaration replacing `declaration`.
+    pub(crate) fn declaration(&self, declaration: &str) -> String {
+        let Ok(decl) = parse_declaration(declaration) else {
+            return declaration.to_string();
+        };
+        let ty = match &self.ty {
+            Some(ty) => ty.clone(),
+            None => decl.ty.to_string(),
+        };
+        let ty = match (self.branded, parse_type(&ty)) {
+            (false, _) => ty,
+            (true, Ok(TsType::Union(_))) => format!("({ty}) & {{ __brand: \"{}\" }}", decl.name),
+            (true, _) => format!("{ty} & {{ __brand: \"{}\" }}", decl.name),
+        };
+        let generics = match decl.generics.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", decl.generics.join(", ")),
+        };
+        format!("type {}{generics} = {ty};", decl.name)
+    }
+}
+
+impl From<&str> for TypeOverride {
+    fn from(ty: &str) -> Self {
+        Self::new(ty)
+    }
+}
+
+impl From<String> for TypeOverride {
+    fn from(ty: String) -> Self {
+        Self::new(ty)
+    }
+}
+
+/// A type passed to [`Api::override_type`](crate::Api::override_type) which its `TS` impl
+/// inlines, like `uuid::Uuid` as `string`. Parameters and responses of the type, an `Option` or
+/// a `Vec` of it reference a declaration named after it instead, `type Uuid = string;`, which
+/// is overridden like the declarations of other types.
+#[derive(Debug, Clone)]
+pub(crate) struct InlinedType {
+    pub(crate) rust_type: &'static str,
+    pub(crate) id: ts_rs::Id,
+    // the type the `TS` impl inlines
+    pub(crate) ts_type: String,
+}
+
+impl InlinedType {
+    pub(crate) fn new<T: ts_rs::TS>() -> Self {
+        Self {
+            rust_type: std::any::type_name::<T>(),
+            id: T::id(),
+            ts_type: T::name_with_generics(),
+        }
+    }
+
+    /// The name of the declaration, the rust type without its path and generics.
+    pub(crate) fn name(&self) -> &'static str {
+        let path = self.rust_type.split('<').next().unwrap_or_default();
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
+    pub(crate) fn declaration(&self) -> String {
+        format!("type {} = {};", self.name(), self.ts_type)
+    }
+
+    /// The type ids of the type, an `Option` and a `Vec` of it, paired with their typescript
+    /// type referencing the declaration.
+    pub(crate) fn uses<T: 'static>(&self) -> [(std::any::TypeId, String); 3] {
+        let name = self.name();
+        [
+            (std::any::TypeId::of::<T>(), name.to_string()),
+            (std::any::TypeId::of::<Option<T>>(), format!("{name} | null")),
+            (std::any::TypeId::of::<Vec<T>>(), format!("Array<{name}>")),
+        ]
+    }
+}
+
+/// Generates the functions converting values whose types contain a declaration with a
+/// converter, either reviving responses or serializing arguments.
+pub(crate) struct Converters {
+    // declaration name -> parsed declaration
+    declarations: BTreeMap<String, TsDeclaration>,
+    // declaration name -> typescript function converting its values
+    custom: BTreeMap<String, String>,
+    // declarations converting values whatever their generics are
+    converting: BTreeSet<String>,
+    // generic declarations converting values of their generics
+    generic_converting: BTreeSet<String>,
+}
+
+impl Converters {
+    /// Takes the declarations paired with their converter, if they have one.
+    /// Declarations which can't be parsed are skipped, their values are left as they are.
+    pub(crate) fn new<'a>(
+        declarations: impl IntoIterator<Item = (&'a String, Option<&'a String>)>,
+    ) -> Self {
+        let mut converters = Self {
+            declarations: BTreeMap::new(),
+            custom: BTreeMap::new(),
+            converting: BTreeSet::new(),
+            generic_converting: BTreeSet::new(),
+        };
+        for (declaration, custom) in declarations {
+            let Ok(decl) = parse_declaration(declaration) else {
+                continue;
+            };
+            if let Some(custom) = custom {
+                converters.custom.insert(decl.name.clone(), custom.clone());
+                converters.converting.insert(decl.name.clone());
+            }
+            converters.declarations.insert(decl.name.clone(), decl);
+        }
+
+        // Declarations convert values if their types reach a custom converter,
+        // which may go through other declarations in any order.
+        loop {
+            let mut converting = Vec::new();
+            let mut generic_converting = Vec::new();
+            for decl in converters.declarations.values() {
+                if converters.custom.contains_key(&decl.name) {
+                    continue;
+                }
+                if !converters.converting.contains(&decl.name)
+                    && converters.expr(&decl.ty, &[], "").is_some()
+                {
+                    converting.push(decl.name.clone());
+                }
+                if !decl.generics.is_empty()
+                    && !converters.generic_converting.contains(&decl.name)
+                    && converters.expr(&decl.ty, &decl.generics, "").is_some()
+                {
+                    generic_converting.push(decl.name.clone());
+                }
+            }
+            if converting.is_empty() && generic_converting.is_empty() {
+                break;
+            }
+            converters.converting.extend(converting);
+            converters.generic_converting.extend(generic_converting);
+        }
+        converters
+    }
+
+    /// A namespace holding the converter of every declaration converting values.
+    /// Generic declarations become functions from the converters of their generics.
+    pub(crate) fn namespace(&self, name: &str) -> String {
+        let mut namespace = format!("export namespace {name} {{\n");
+        for decl in self.declarations.values() {
+            if let Some(custom) = self.custom.get(&decl.name) {
+                namespace += &format!(
+                    "        export const {}: __convert.Convert = __convert.custom({custom});\n",
+                    decl.name
+                );
+            } else if decl.generics.is_empty() {
+                if let Some(expr) = self.expr(&decl.ty, &[], "") {
+                    namespace += &format!(
+                        "        export const {}: __convert.Convert = {expr};\n",
+                        decl.name
+                    );
+                }
+            } else {
+                let expr = self
+                    .expr(&decl.ty, &decl.generics, "")
+                    .unwrap_or_else(|| "__convert.identity".to_string());
+                if self.generic_converting.contains(&decl.name) {
+                    let params = decl
+                        .generics
+                        .iter()
+                        .map(|g| format!("{g}: __convert.Convert"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    namespace += &format!(
+                        "        export const {} = ({params}): __convert.Convert => {expr};\n",
+                        decl.name
+                    );
+                }
+            }
+        }
+        namespace += "    }";
+        namespace
+    }
+
+    /// The converter of a type used outside of the declarations, e.g. a parameter type,
+    /// or `None` if its values are left as they are.
+    /// References to declarations are qualified with `prefix`.
+    pub(crate) fn type_expr(&self, ty: &str, prefix: &str) -> Option<String> {
+        self.expr(&parse_type(ty).ok()?, &[], prefix)
+    }
+
+    /// `generics` are the generics of the declaration `ty` is in, which are converters.
+    fn expr(&self, ty: &TsType, generics: &[String], prefix: &str) -> Option<String> {
+        // the converters of `types` if any of them converts values
+        let list = |types: &[TsType]| {
+            let exprs: Vec<_> = types
+                .iter()
+                .map(|t| self.expr(t, generics, prefix))
+                .collect();
+            exprs.iter().any(Option::is_some).then(|| {
+                exprs
+                    .into_iter()
+                    .map(|e| e.unwrap_or_else(|| "__convert.identity".to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+        };
+        // Values of unions and intersections go through the converters of all parts,
+        // which only convert the values and fields of their own type.
+        let all = |types: &[TsType]| {
+            let mut exprs: Vec<_> = types
+                .iter()
+                .filter_map(|t| self.expr(t, generics, prefix))
+                .collect();
+            match exprs.len() {
+                0 => None,
+                1 => exprs.pop(),
+                _ => Some(format!("__convert.all([{}])", exprs.join(", "))),
+            }
+        };
+
+        match ty {
+            TsType::Keyword(_) | TsType::Literal(_) => None,
+            TsType::Array(t) => self
+                .expr(t, generics, prefix)
+                .map(|e| format!("__convert.array({e})")),
+            TsType::Tuple(ts) => list(ts).map(|l| format!("__convert.tuple([{l}])")),
+            TsType::Union(ts) | TsType::Intersection(ts) => all(ts),
+            TsType::Index(_, v) => self
+                .expr(v, generics, prefix)
+                .map(|e| format!("__convert.record({e})")),
+            TsType::Object(fields) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .filter_map(|f| {
+                        let expr = self.expr(&f.ty, generics, prefix)?;
+                        Some(format!("{}: {expr}", field_name(&f.name)))
+                    })
+                    .collect();
+                (!fields.is_empty())
+                    .then(|| format!("__convert.object({{ {} }})", fields.join(", ")))
+            }
+            TsType::Reference(name, args) if args.is_empty() && generics.contains(name) => {
+                Some(name.clone())
+            }
+            TsType::Reference(name, args) if name == "Record" && args.len() == 2 => self
+                .expr(&args[1], generics, prefix)
+                .map(|e| format!("__convert.record({e})")),
+            TsType::Reference(name, args) => {
+                let decl = self.declarations.get(name)?;
+                if decl.generics.is_empty() || self.custom.contains_key(name) {
+                    return self
+                        .converting
+                        .contains(name)
+                        .then(|| format!("__convert.lazy(() => {prefix}{name})"));
+                }
+                let args = list(args).filter(|_| self.generic_converting.contains(name));
+                match args {
+                    Some(args) => Some(format!("__convert.lazy(() => {prefix}{name}({args}))")),
+                    None if self.converting.contains(name) => {
+                        let identities = vec!["__convert.identity"; decl.generics.len()];
+                        Some(format!(
+                            "__convert.lazy(() => {prefix}{name}({}))",
+                            identities.join(", ")
+                        ))
+                    }
+                    None => None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_declaration() {
+    assert_eq!(
+        TypeOverride::new("Date").declaration("type Timestamp = string;"),
+        "type Timestamp = Date;"
+    );
+    assert_eq!(
+        TypeOverride::branded().declaration("type UserId = string;"),
+        "type UserId = string & { __brand: \"UserId\" };"
+    );
+    assert_eq!(
+        TypeOverride::branded().declaration("type Id<T> = string | number;"),
+        "type Id<T> = (string | number) & { __brand: \"Id\" };"
+    );
+}
+
+#[test]
+fn test_converters() {
+    let declarations = [
+        "type Timestamp = string;".to_string(),
+        "type Event = { name: string, at: Timestamp, tags: Array<string>, };".to_string(),
+        "type Page<T> = { items: Array<T>, total: number, };".to_string(),
+        "type Tree = { children: Array<Tree>, };".to_string(),
+    ];
+    let revive = "(value: string) => new Date(value)".to_string();
+    let converters = Converters::new(
+        declarations
+            .iter()
+            .map(|d| (d, d.starts_with("type Timestamp").then_some(&revive))),
+    );
+
+    assert_eq!(
+        converters.type_expr("Page<Event> | null", "list.__revive."),
+        Some(
+            "__convert.lazy(() => list.__revive.Page(__convert.lazy(() => list.__revive.Event)))"
+                .to_string()
+        )
+    );
+    assert_eq!(converters.type_expr("Page<string>", "list.__revive."), None);
+    assert_eq!(converters.type_expr("Tree", ""), None);
+    assert_eq!(
+        converters.type_expr("[number, Timestamp]", ""),
+        Some("__convert.tuple([__convert.identity, __convert.lazy(() => Timestamp)])".to_string())
+    );
+
+    let namespace = converters.namespace("__revive");
+    assert!(namespace.contains(
+        "export const Timestamp: __convert.Convert = __convert.custom((value: string) => new Date(value));"
+    ));
+    assert!(namespace.contains(
+        "export const Event: __convert.Convert = __convert.object({ at: __convert.lazy(() => Timestamp) });"
+    ));
+    assert!(namespace.contains(
+        "export const Page = (T: __convert.Convert): __convert.Convert => __convert.object({ items: __convert.array(T) });"
+    ));
+    assert!(!namespace.contains("Tree"));
+}
@@ -0,0 +1,311 @@
+//! File parameters sent as `multipart/form-data`, see [`Upload`] and the `multipart` feature.
+//!
+//! The arguments are sent as json in the `arguments` part, with the name of the part holding
+//! the file in place of every `Upload`. The typescript client names the parts after the parameters.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Multipart, Request};
+use axum::http::StatusCode;
+use axum::response::Response;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::inputs::{self, WithInputs};
+use crate::outcome::{reject, Outcome};
+
+/// A file sent as a part of a `multipart/form-data` request, typed as `Blob | File` in typescript.
+///
+/// Files larger than [`Uploads::memory_limit`] are streamed to a temporary file, which is
+/// removed when the `Upload` is dropped unless it is [`persist`](Upload::persist)ed.
+///
+/// ```ignore
+/// #[ts_export]
+/// async fn upload_avatar(user_id: u32, avatar: Upload) -> Result<(), String> {
+///     avatar
+///         .persist(format!("avatars/{user_id}"))
+///         .await
+///         .map_err(|e| e.to_string())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Upload {
+    file_name: Option<String>,
+    content_type: Option<String>,
+    len: u64,
+    data: Data,
+}
+
+#[derive(Debug)]
+enum Data {
+    Memory(Bytes),
+    Disk(TempFile),
+}
+
+// removed when dropped
+#[derive(Debug)]
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl Upload {
+    /// The file name sent by the client, e.g. the name of a `File` in typescript.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The temporary file the upload was streamed to, `None` if it is held in memory.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            Data::Memory(_) => None,
+            Data::Disk(file) => Some(&file.0),
+        }
+    }
+
+    /// The content of the file, read from disk if it was streamed there.
+    pub async fn bytes(&self) -> io::Result<Bytes> {
+        match &self.data {
+            Data::Memory(bytes) => Ok(bytes.clone()),
+            Data::Disk(file) => tokio::fs::read(&file.0).await.map(Bytes::from),
+        }
+    }
+
+    /// Moves the file to `path`, replacing the file there.
+    pub async fn persist(self, path: impl AsRef<Path>) -> io::Result<()> {
+        match &self.data {
+            Data::Memory(bytes) => tokio::fs::write(path, bytes).await,
+            // renaming fails across file systems
+            Data::Disk(file) => match tokio::fs::rename(&file.0, &path).await {
+                Ok(()) => Ok(()),
+                Err(_) => tokio::fs::copy(&file.0, path).await.map(|_| ()),
+            },
+        }
+    }
+}
+
+thread_local! {
+    // part name -> file, while the arguments of a multipart request are deserialized
+    static UPLOADS: RefCell<HashMap<String, Upload>> = RefCell::new(HashMap::new());
+}
+
+impl<'de> Deserialize<'de> for Upload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let part = String::deserialize(deserializer)?;
+        UPLOADS
+            .with(|uploads| uploads.borrow_mut().remove(&part))
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "no file in the part `{part}`, files are sent as multipart/form-data"
+                ))
+            })
+    }
+}
+
+/// Limits of uploaded files and where they are stored, see [`Api::uploads`].
+///
+/// The size of the whole request is limited like any other body, to 2 MB by default.
+/// Raise it for a function with `DefaultBodyLimit`:
+///
+/// ```ignore
+/// let api = Api::new()
+///     .uploads(Uploads::new().max_file_size(50 << 20).dir("/var/tmp/uploads"))
+///     .register_axum_with(upload_video, DefaultBodyLimit::max(50 << 20));
+/// ```
+///
+/// [`Api::uploads`]: crate::Api::uploads
+#[derive(Debug, Clone)]
+pub struct Uploads {
+    max_file_size: Option<u64>,
+    memory_limit: usize,
+    dir: Option<PathBuf>,
+}
+
+impl Default for Uploads {
+    fn default() -> Self {
+        Self {
+            max_file_size: None,
+            memory_limit: 1 << 20,
+            dir: None,
+        }
+    }
+}
+
+impl Uploads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects files larger than `max_file_size` bytes with `413 Payload Too Large`.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Streams files larger than `memory_limit` bytes to disk, 1 MiB by default.
+    pub fn memory_limit(mut self, memory_limit: usize) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    /// The directory of the temporary files, `std::env::temp_dir()` by default.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    fn temp_file(&self) -> TempFile {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let dir = self.dir.clone().unwrap_or_else(std::env::temp_dir);
+        TempFile(dir.join(format!(
+            "ts-rpc-upload-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        )))
+    }
+}
+
+pub(crate) fn is_multipart(request: &Request) -> bool {
+    request
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"))
+}
+
+fn invalid(message: String) -> Response {
+    reject(
+        (StatusCode::UNPROCESSABLE_ENTITY, message),
+        Outcome::InvalidArguments,
+    )
+}
+
+/// Decodes the arguments of a `multipart/form-data` request, reading the files first.
+pub(crate) async fn decode<T: DeserializeOwned>(request: Request) -> Result<T, Response> {
+    let uploads = request
+        .extensions()
+        .get::<Uploads>()
+        .cloned()
+        .unwrap_or_default();
+    let values = inputs::values(&request);
+    let mut multipart = Multipart::from_request(request, &())
+        .await
+        .map_err(|e| reject(e, Outcome::InvalidArguments))?;
+
+    let mut arguments = None;
+    let mut files = HashMap::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| reject(e, Outcome::InvalidArguments))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "arguments" && field.file_name().is_none() {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| reject(e, Outcome::InvalidArguments))?;
+            arguments = Some(text);
+        } else {
+            let upload = read_file(&uploads, &name, field).await?;
+            files.insert(name, upload);
+        }
+    }
+    let arguments = arguments.ok_or_else(|| invalid("Missing the `arguments` part".to_string()))?;
+
+    UPLOADS.with(|uploads| *uploads.borrow_mut() = files);
+    let result = inputs::with(values, || serde_json::from_str(&arguments));
+    // files not taken by the arguments are removed
+    UPLOADS.with(|uploads| uploads.borrow_mut().clear());
+    result
+        .map(|WithInputs(value)| value)
+        .map_err(|e| invalid(format!("Failed to deserialize the arguments: {e}")))
+}
+
+async fn read_file(
+    uploads: &Uploads,
+    name: &str,
+    mut field: axum::extract::multipart::Field<'_>,
+) -> Result<Upload, Response> {
+    let failed = |e: io::Error| {
+        reject(
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Outcome::Failed,
+        )
+    };
+
+    let file_name = field.file_name().map(str::to_string);
+    let content_type = field.content_type().map(str::to_string);
+    let mut len = 0;
+    let mut buffer = Vec::new();
+    let mut disk: Option<(TempFile, tokio::fs::File)> = None;
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| reject(e, Outcome::InvalidArguments))?
+    {
+        len += chunk.len() as u64;
+        if let Some(max) = uploads.max_file_size.filter(|max| len > *max) {
+            return Err(reject(
+                (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("The file `{name}` is larger than the limit of {max} bytes"),
+                ),
+                Outcome::InvalidArguments,
+            ));
+        }
+        match &mut disk {
+            Some((_, file)) => file.write_all(&chunk).await.map_err(failed)?,
+            None if buffer.len() + chunk.len() > uploads.memory_limit => {
+                let temp = uploads.temp_file();
+                let mut file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&temp.0)
+                    .await
+                    .map_err(failed)?;
+                file.write_all(&buffer).await.map_err(failed)?;
+                file.write_all(&chunk).await.map_err(failed)?;
+                buffer = Vec::new();
+                disk = Some((temp, file));
+            }
+            None => buffer.extend_from_slice(&chunk),
+        }
+    }
+
+    let data = match disk {
+        Some((temp, mut file)) => {
+            file.flush().await.map_err(failed)?;
+            Data::Disk(temp)
+        }
+        None => Data::Memory(Bytes::from(buffer)),
+    };
+    Ok(Upload {
+        file_name,
+        content_type,
+        len,
+        data,
+    })
+}
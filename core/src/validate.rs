@@ -0,0 +1,428 @@
+//! Constraints on the fields of request types, checked before the function is called,
+//! see `#[derive(Validate)]`.
+//!
+//! The constraints are exported to typescript as `__constraints` in the namespace of every
+//! function using the type, with the names of the matching `<input>` attributes. With
+//! `Api::ts_validation` the client checks them too, with the same messages as the server.
+
+use std::any::{Any, TypeId};
+#[cfg(feature = "axum-router")]
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// A request type whose values are checked after deserializing them, implemented by
+/// `#[derive(Validate)]`:
+///
+/// ```ignore
+/// #[derive(Deserialize, TS, Validate)]
+/// struct SignUp {
+///     #[validate(email)]
+///     email: String,
+///     #[validate(length(min = 8, max = 64))]
+///     password: String,
+///     #[validate(range(min = 13))]
+///     age: u8,
+///     #[validate(nested)]
+///     address: Option<Address>,
+/// }
+/// ```
+///
+/// Parameters of a type deriving `Validate`, an `Option` or a `Vec` of it are validated before
+/// the function is called, responding with `422 Unprocessable Entity` and the [`FieldError`]s.
+/// Types nested deeper in a parameter, e.g. `HashMap<String, SignUp>`, are only validated in a
+/// `#[validate(nested)]` field. The type has to implement `TS` unless it is generic.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Vec<FieldError>>;
+}
+
+/// A value breaking a [`Constraint`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FieldError {
+    // name of the parameter, filled in for the response of functions using `#[ts_export]`
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub parameter: Option<&'static str>,
+    // serialized field names and indices separated by dots, e.g. `addresses.0.zip`
+    pub path: String,
+    // `email`, `url`, `length` or `range`
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// A constraint on a field, set with `#[validate(..)]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint {
+    /// `#[validate(email)]`, the text contains one `@` with a dotted domain after it.
+    Email,
+    /// `#[validate(url)]`, the text starts with a scheme followed by `://` and a host.
+    Url,
+    /// `#[validate(length(min = .., max = ..))]`, in characters for text, items for lists.
+    Length {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// `#[validate(range(min = .., max = ..))]`, inclusive.
+    Range { min: Option<f64>, max: Option<f64> },
+}
+
+impl Constraint {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Constraint::Email => "email",
+            Constraint::Url => "url",
+            Constraint::Length { .. } => "length",
+            Constraint::Range { .. } => "range",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            Constraint::Email => "must be an email address".to_string(),
+            Constraint::Url => "must be a url".to_string(),
+            Constraint::Length { min, max } => match (min, max) {
+                (Some(min), Some(max)) => format!("must have a length between {min} and {max}"),
+                (Some(min), None) => format!("must have a length of at least {min}"),
+                (None, Some(max)) => format!("must have a length of at most {max}"),
+                (None, None) => String::new(),
+            },
+            Constraint::Range { min, max } => match (min, max) {
+                (Some(min), Some(max)) => format!("must be between {min} and {max}"),
+                (Some(min), None) => format!("must be at least {min}"),
+                (None, Some(max)) => format!("must be at most {max}"),
+                (None, None) => String::new(),
+            },
+        }
+    }
+
+    /// The constraint as the properties of a typescript object, e.g. `minLength: 1, maxLength: 8`.
+    pub(crate) fn ts_properties(&self) -> String {
+        let properties = match *self {
+            Constraint::Email => vec![("email", Some("true".to_string()))],
+            Constraint::Url => vec![("url", Some("true".to_string()))],
+            Constraint::Length { min, max } => vec![
+                ("minLength", min.map(|min| min.to_string())),
+                ("maxLength", max.map(|max| max.to_string())),
+            ],
+            Constraint::Range { min, max } => vec![
+                ("min", min.map(|min| min.to_string())),
+                ("max", max.map(|max| max.to_string())),
+            ],
+        };
+        properties
+            .into_iter()
+            .filter_map(|(name, value)| Some(format!("{name}: {}", value?)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn breaks(&self, value: &Checked) -> bool {
+        match (*self, value) {
+            (Constraint::Email, Checked::Text(text)) => !is_email(text),
+            (Constraint::Url, Checked::Text(text)) => !is_url(text),
+            (Constraint::Length { min, max }, Checked::Length(len)) => {
+                min.is_some_and(|min| *len < min) || max.is_some_and(|max| *len > max)
+            }
+            (Constraint::Range { min, max }, Checked::Number(n)) => {
+                min.is_some_and(|min| *n < min) || max.is_some_and(|max| *n > max)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.split('.').count() > 1
+        && domain.split('.').all(|label| !label.is_empty())
+        && !text.contains(char::is_whitespace)
+}
+
+fn is_url(text: &str) -> bool {
+    let Some((scheme, rest)) = text.split_once("://") else {
+        return false;
+    };
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !rest.starts_with('/')
+        && !text.contains(char::is_whitespace)
+}
+
+/// What a constraint checks of a value.
+pub enum Checked<'a> {
+    Text(&'a str),
+    Length(usize),
+    Number(f64),
+}
+
+/// A field type constraints apply to. `None` is always valid.
+pub trait Constrained {
+    fn checked(&self) -> Option<Checked<'_>>;
+}
+
+impl Constrained for String {
+    fn checked(&self) -> Option<Checked<'_>> {
+        Some(Checked::Text(self))
+    }
+}
+
+impl Constrained for str {
+    fn checked(&self) -> Option<Checked<'_>> {
+        Some(Checked::Text(self))
+    }
+}
+
+impl<T> Constrained for Vec<T> {
+    fn checked(&self) -> Option<Checked<'_>> {
+        Some(Checked::Length(self.len()))
+    }
+}
+
+impl<T: Constrained> Constrained for Option<T> {
+    fn checked(&self) -> Option<Checked<'_>> {
+        self.as_ref().and_then(T::checked)
+    }
+}
+
+impl<T: Constrained + ?Sized> Constrained for Box<T> {
+    fn checked(&self) -> Option<Checked<'_>> {
+        (**self).checked()
+    }
+}
+
+macro_rules! impl_constrained_number {
+    ($($t:ty),*) => {
+        $(
+            impl Constrained for $t {
+                fn checked(&self) -> Option<Checked<'_>> {
+                    Some(Checked::Number(*self as f64))
+                }
+            }
+        )*
+    };
+}
+
+impl_constrained_number!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/// Adds an error at `path` if `value` breaks `constraint`. Used by `#[derive(Validate)]`.
+pub fn check<T: Constrained + ?Sized>(
+    errors: &mut Vec<FieldError>,
+    path: &str,
+    value: &T,
+    constraint: Constraint,
+) {
+    // Text is checked by length in characters
+    let checked = match (value.checked(), constraint) {
+        (Some(Checked::Text(text)), Constraint::Length { .. }) => {
+            Some(Checked::Length(text.chars().count()))
+        }
+        (checked, _) => checked,
+    };
+    if checked.is_some_and(|checked| constraint.breaks(&checked)) {
+        errors.push(FieldError {
+            parameter: None,
+            path: path.to_string(),
+            code: constraint.code(),
+            message: constraint.message(),
+        });
+    }
+}
+
+/// Adds the errors of a `#[validate(nested)]` field, prefixed with its path.
+pub fn nested<T: Validate + ?Sized>(errors: &mut Vec<FieldError>, path: &str, value: &T) {
+    if let Err(nested) = value.validate() {
+        errors.extend(nested.into_iter().map(|error| FieldError {
+            path: format!("{path}.{}", error.path),
+            ..error
+        }));
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        self.as_ref().map_or(Ok(()), T::validate)
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        for (i, item) in self.iter().enumerate() {
+            nested(&mut errors, &i.to_string(), item);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl<T: Validate + ?Sized> Validate for Box<T> {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        (**self).validate()
+    }
+}
+
+/// Validates a value of the type of a parameter.
+pub type Validator = fn(&dyn Any) -> Result<(), Vec<FieldError>>;
+
+/// The constraints of a type deriving `Validate`, collected to validate parameters and
+/// to export the constraints to typescript.
+pub struct TypeConstraints {
+    // typescript name of the type
+    pub name: &'static str,
+    // the id the declarations of functions are keyed by, see `TsFn::type_declarations`
+    pub id: fn() -> ts_rs::Id,
+    // type ids of the type, an `Option` and a `Vec` of it -> their validator
+    pub validators: fn() -> [(TypeId, Validator); 3],
+    // typescript field name -> constraints
+    pub fields: &'static [(&'static str, &'static [Constraint])],
+}
+inventory::collect!(TypeConstraints);
+
+#[doc(hidden)]
+pub fn ts_id<T: ts_rs::TS>() -> ts_rs::Id {
+    T::id()
+}
+
+#[doc(hidden)]
+pub fn validators<T: Validate + 'static>() -> [(TypeId, Validator); 3] {
+    fn validate<T: Validate + 'static>(value: &dyn Any) -> Result<(), Vec<FieldError>> {
+        value.downcast_ref::<T>().unwrap().validate()
+    }
+    [
+        (TypeId::of::<T>(), validate::<T>),
+        (TypeId::of::<Option<T>>(), validate::<Option<T>>),
+        (TypeId::of::<Vec<T>>(), validate::<Vec<T>>),
+    ]
+}
+
+#[cfg(feature = "axum-router")]
+static VALIDATORS: once_cell::sync::Lazy<std::collections::HashMap<TypeId, Validator>> =
+    once_cell::sync::Lazy::new(|| {
+        inventory::iter::<TypeConstraints>()
+            .flat_map(|c| (c.validators)())
+            .collect()
+    });
+
+/// The constraints of the type whose declarations are keyed by `id`.
+pub(crate) fn constraints(id: &ts_rs::Id) -> Option<&'static TypeConstraints> {
+    inventory::iter::<TypeConstraints>().find(|c| (c.id)() == *id)
+}
+
+/// The parameter names of a function with parameters to validate, added to its requests as an
+/// extension to name the parameters in the errors.
+#[cfg(feature = "axum-router")]
+#[derive(Debug, Clone)]
+pub(crate) struct Parameters(Arc<[&'static str]>);
+
+#[cfg(feature = "axum-router")]
+impl Parameters {
+    pub(crate) fn of(ts_fn: &crate::TsFn) -> Option<Self> {
+        let params = &ts_fn.request_types;
+        params
+            .iter()
+            .any(|p| p.type_id.is_some_and(|id| VALIDATORS.contains_key(&id)))
+            .then(|| Parameters(params.iter().map(|p| p.name).collect()))
+    }
+}
+
+/// Validates the arguments of a call whose types derive `Validate`, by position, naming them
+/// after `parameters`.
+#[cfg(feature = "axum-router")]
+pub(crate) fn validate_arguments(
+    parameters: Option<&Parameters>,
+    arguments: &[&dyn Any],
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    for (i, argument) in arguments.iter().enumerate() {
+        let Some(validate) = VALIDATORS.get(&(**argument).type_id()) else {
+            continue;
+        };
+        if let Err(argument_errors) = validate(*argument) {
+            let parameter = parameters.and_then(|p| p.0.get(i).copied());
+            errors.extend(
+                argument_errors
+                    .into_iter()
+                    .map(|error| FieldError { parameter, ..error }),
+            );
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Responds with `422 Unprocessable Entity` and `{ "kind", "message", "errors" }` as json.
+#[cfg(feature = "axum-router")]
+pub(crate) fn reject(errors: Vec<FieldError>) -> axum::response::Response {
+    #[derive(Serialize)]
+    struct Rejection {
+        kind: &'static str,
+        message: &'static str,
+        errors: Vec<FieldError>,
+    }
+
+    let rejection = Rejection {
+        kind: "validation",
+        message: "Invalid arguments",
+        errors,
+    };
+    crate::outcome::reject(
+        (
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            axum::Json(rejection),
+        ),
+        crate::outcome::Outcome::InvalidArguments,
+    )
+}
+
+#[test]
+fn test_constraints() {
+    let mut errors = Vec::new();
+    check(&mut errors, "email", "a@b.co", Constraint::Email);
+    check(&mut errors, "email", "a@b", Constraint::Email);
+    check(&mut errors, "site", "https://a.io/x", Constraint::Url);
+    check(&mut errors, "site", "a.io", Constraint::Url);
+    let length = Constraint::Length {
+        min: Some(2),
+        max: Some(3),
+    };
+    check(&mut errors, "name", "üüü", length);
+    check(&mut errors, "name", &Some("üüüü".to_string()), length);
+    check(&mut errors, "tags", &vec![1], length);
+    check(&mut errors, "nick", &None::<String>, length);
+    let range = Constraint::Range {
+        min: Some(0.5),
+        max: None,
+    };
+    check(&mut errors, "ratio", &0.5, range);
+    check(&mut errors, "age", &0u8, range);
+
+    let paths: Vec<_> = errors.iter().map(|e| (e.path.as_str(), e.code)).collect();
+    assert_eq!(
+        paths,
+        [
+            ("email", "email"),
+            ("site", "url"),
+            ("name", "length"),
+            ("tags", "length"),
+            ("age", "range")
+        ]
+    );
+    assert_eq!(errors[2].message, "must have a length between 2 and 3");
+    assert_eq!(errors[4].message, "must be at least 0.5");
+    assert_eq!(length.ts_properties(), "minLength: 2, maxLength: 3");
+    assert_eq!(range.ts_properties(), "min: 0.5");
+}
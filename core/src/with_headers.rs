@@ -0,0 +1,265 @@
+//! Headers and cookies set by functions next to their typed response, see [`WithHeaders`].
+
+use std::cell::RefCell;
+use std::fmt;
+use std::time::Duration;
+
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use axum::response::Response;
+use serde::{Serialize, Serializer};
+
+/// The response of a function with headers to send along, typed as `T` in typescript.
+///
+/// Headers listed in `#[ts_export(response_header = "..")]` are typed in the `withMeta` variant
+/// of the function in the typescript client, which resolves to `{ data, headers }`.
+///
+/// ```ignore
+/// #[ts_export(response_header = "x-total-count")]
+/// async fn list_orders(page: u32) -> WithHeaders<Vec<Order>> {
+///     let (orders, total) = db::orders(page).await;
+///     WithHeaders::new(orders).header("X-Total-Count", total)
+/// }
+///
+/// #[ts_export]
+/// async fn greet(name: String) -> Result<WithHeaders<()>, String> {
+///     // values from requests may be invalid header values
+///     WithHeaders::new(()).try_header("x-name", name).map_err(|e| e.to_string())
+/// }
+///
+/// #[ts_export]
+/// async fn login(email: String, password: String) -> Result<WithHeaders<User>, String> {
+///     let (user, session) = auth::login(&email, &password).await?;
+///     Ok(WithHeaders::new(user).cookie(SetCookie::new("session", session).http_only().secure()))
+/// }
+/// ```
+///
+/// It serializes as `data`, so it can be nested in the response like in a `Result`.
+#[derive(Debug)]
+pub struct WithHeaders<T> {
+    pub data: T,
+    pub headers: HeaderMap,
+}
+
+impl<T> WithHeaders<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Adds a header, replacing the response header of the same name. Names are case
+    /// insensitive.
+    ///
+    /// Panics if `name` isn't a header name or `value` isn't a valid header value, e.g.
+    /// contains a newline. Values from requests or other data should go through `try_header`.
+    pub fn header(self, name: impl AsRef<str>, value: impl fmt::Display) -> Self {
+        self.try_header(name, value).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Adds a header like `header`, failing if the name or the value isn't valid.
+    pub fn try_header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl fmt::Display,
+    ) -> Result<Self, InvalidHeader> {
+        let invalid = || InvalidHeader(name.as_ref().to_string());
+        let header_name =
+            HeaderName::try_from(name.as_ref().to_ascii_lowercase()).map_err(|_| invalid())?;
+        let value = HeaderValue::try_from(value.to_string()).map_err(|_| invalid())?;
+        self.headers.append(header_name, value);
+        Ok(self)
+    }
+
+    /// Adds a `Set-Cookie` header.
+    ///
+    /// Panics if the cookie isn't a valid header value, e.g. its path contains a newline.
+    /// Cookies from requests or other data should go through `try_cookie`.
+    pub fn cookie(self, cookie: SetCookie) -> Self {
+        self.try_cookie(cookie).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Adds a `Set-Cookie` header like `cookie`, failing if the cookie isn't valid.
+    pub fn try_cookie(mut self, cookie: SetCookie) -> Result<Self, InvalidHeader> {
+        let value = HeaderValue::try_from(cookie.to_string())
+            .map_err(|_| InvalidHeader(header::SET_COOKIE.to_string()))?;
+        self.headers.append(header::SET_COOKIE, value);
+        Ok(self)
+    }
+}
+
+/// A header of a [`WithHeaders`] with an invalid name or value, by its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHeader(pub String);
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid name or value of the header `{}`", self.0)
+    }
+}
+
+impl std::error::Error for InvalidHeader {}
+
+thread_local! {
+    // the headers of the `WithHeaders` serialized into the response being built
+    static HEADERS: RefCell<HeaderMap> = RefCell::new(HeaderMap::new());
+}
+
+impl<T: Serialize> Serialize for WithHeaders<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HEADERS.with(|headers| headers.borrow_mut().extend(self.headers.clone()));
+        self.data.serialize(serializer)
+    }
+}
+
+/// Runs `respond`, which serializes the response, and adds the headers of the `WithHeaders`
+/// it serialized.
+pub(crate) fn collect(respond: impl FnOnce() -> Response) -> Response {
+    HEADERS.with(|headers| headers.borrow_mut().clear());
+    let mut response = respond();
+    let headers = HEADERS.with(|headers| std::mem::take(&mut *headers.borrow_mut()));
+    response.headers_mut().extend(headers);
+    response
+}
+
+/// A cookie set by a function, see [`WithHeaders::cookie`].
+///
+/// The value is percent encoded, which `#[cookie]` parameters decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub max_age: Option<Duration>,
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: Option<SameSite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SetCookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
+        }
+    }
+
+    /// Removes the cookie from the client, expiring it right away.
+    /// The path and domain have to match the ones it was set with.
+    pub fn remove(name: impl Into<String>) -> Self {
+        Self::new(name, "").max_age(Duration::ZERO)
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Keeps the cookie for `max_age` instead of the browser session, in whole seconds.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Hides the cookie from scripts in browsers.
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    /// Only sends the cookie over https.
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+/// The value of the `Set-Cookie` header.
+impl fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.name)?;
+        for b in self.value.bytes() {
+            match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    write!(f, "{}", b as char)?
+                }
+                _ => write!(f, "%{b:02X}")?,
+            }
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={domain}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => write!(f, "; SameSite=Strict"),
+            Some(SameSite::Lax) => write!(f, "; SameSite=Lax"),
+            Some(SameSite::None) => write!(f, "; SameSite=None"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_header() {
+    let with_headers = WithHeaders::new(()).header("X-Total-Count", 42);
+    assert_eq!(with_headers.headers["x-total-count"], "42");
+    assert!(WithHeaders::new(()).try_header("x-name", "a\nb").is_err());
+    assert!(WithHeaders::new(()).try_header("x name", "a").is_err());
+    assert!(WithHeaders::new(())
+        .try_cookie(SetCookie::new("session", "a").path("/\n"))
+        .is_err());
+}
+
+#[test]
+fn test_set_cookie() {
+    assert_eq!(SetCookie::new("session", "abc").to_string(), "session=abc");
+    assert_eq!(
+        SetCookie::new("session", "a b;ü")
+            .path("/")
+            .max_age(Duration::from_secs(3600))
+            .http_only()
+            .secure()
+            .same_site(SameSite::Lax)
+            .to_string(),
+        "session=a%20b%3B%C3%BC; Path=/; Max-Age=3600; HttpOnly; Secure; SameSite=Lax"
+    );
+    assert_eq!(
+        SetCookie::remove("session").to_string(),
+        "session=; Max-Age=0"
+    );
+}
@@ -12,48 +12,210 @@ pub fn ts_export(
     }
 }
 
-fn ts_export_inner(_: TokenStream, input: TokenStream) -> Result<TokenStream, syn::Error> {
-    let f = syn::parse2::<syn::ItemFn>(input)?;
+#[proc_macro_attribute]
+pub fn service(
+    attr: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    match service_inner(TokenStream::from(attr), TokenStream::from(input)) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
 
-    // Check if the function is async
-    if f.sig.asyncness.is_none() {
-        return Err(syn::Error::new_spanned(&f.sig.ident, "Must be async"));
+#[proc_macro_derive(TsDocs)]
+pub fn ts_docs(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match ts_docs_inner(TokenStream::from(input)) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
     }
-    if !f.sig.generics.params.is_empty() {
-        return Err(syn::Error::new_spanned(
-            &f.sig.ident,
-            "Generics not supported",
-        ));
+}
+
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn validate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match validate_inner(TokenStream::from(input)) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
     }
+}
 
-    let signature = &f.sig;
-    let name = &signature.ident;
+/// Arguments of `#[ts_export(..)]`.
+#[derive(Default)]
+struct ExportArgs {
+    sunset: Option<syn::LitStr>,
+    // function returning a tower layer for the route
+    layer: Option<syn::Path>,
+    // functions checking the request context, in order
+    guards: Vec<syn::Path>,
+    // headers of the response typed in the client
+    response_headers: Vec<syn::LitStr>,
+}
+
+impl ExportArgs {
+    fn parse(attr: TokenStream) -> Result<Self, syn::Error> {
+        let mut args = Self::default();
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("sunset") {
+                args.sunset = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("layer") {
+                args.layer = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("guard") {
+                args.guards.push(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("response_header") {
+                args.response_headers.push(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported ts_export argument"))
+            }
+        });
+        syn::parse::Parser::parse2(parser, attr)?;
+        Ok(args)
+    }
+}
+
+fn ts_export_inner(attr: TokenStream, input: TokenStream) -> Result<TokenStream, syn::Error> {
+    let args = ExportArgs::parse(attr)?;
+    let mut f = syn::parse2::<syn::ItemFn>(input)?;
+    let name = f.sig.ident.to_string();
     let static_name = Ident::new(&format!("__{}", name), Span::call_site());
+    let ts_fn = ts_fn(args, &f.attrs, &mut f.sig, &name, &static_name, false)?;
+
+    // `register_axum` finds the function through its marker by the type of the function item.
+    // Referring to the function doesn't warn if it is `#[deprecated]`.
+    let this_crate = get_crate_name("ts_rpc", false);
+    let ident = &f.sig.ident;
+    let marker = Ident::new(&format!("__{name}_marker"), Span::call_site());
+    Ok(quote!(
+        #f
+
+        #ts_fn
+
+        #[doc(hidden)]
+        #[allow(non_camel_case_types, dead_code)]
+        struct #marker;
+
+        #[allow(deprecated)]
+        impl #this_crate::ProcedureMarker for #marker {
+            const PATH: &'static str = concat!(module_path!(), "::", #name);
+
+            fn ts_fn() -> &'static #this_crate::TsFn {
+                &#static_name
+            }
+
+            fn handler() -> ::std::any::TypeId {
+                fn type_id<F: 'static>(_: &F) -> ::std::any::TypeId {
+                    ::std::any::TypeId::of::<F>()
+                }
+                type_id(&#ident)
+            }
+        }
+
+        #this_crate::inventory::submit! {
+            #this_crate::Procedure::of::<#marker>()
+        }
+    ))
+}
+
+/// The `TsFn` of a function or `#[service]` method named `name`, submitted to `inventory`.
+/// Removes our attributes from the parameters.
+fn ts_fn(
+    args: ExportArgs,
+    attrs: &[syn::Attribute],
+    sig: &mut syn::Signature,
+    name: &str,
+    static_name: &Ident,
+    is_method: bool,
+) -> Result<TokenStream, syn::Error> {
+    // Check if the function is async
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(&sig.ident, "Must be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.ident, "Generics not supported"));
+    }
+
+    let fn_docs = docs(attrs);
+    let set_deprecated = match deprecation(attrs)? {
+        Some((note, since)) => {
+            let note = note.map_or(quote!(None), |note| quote!(Some(#note)));
+            let since = since.map_or(quote!(None), |since| quote!(Some(#since)));
+            Some(quote!(ts.set_deprecated(#note, #since);))
+        }
+        None => None,
+    };
+    let set_sunset = match args.sunset {
+        Some(sunset) if set_deprecated.is_none() => {
+            return Err(syn::Error::new_spanned(
+                sunset,
+                "`sunset` requires the function to be `#[deprecated]`",
+            ));
+        }
+        Some(sunset) => Some(quote!(ts.set_sunset(#sunset);)),
+        None => None,
+    };
+
+    let set_layer = args
+        .layer
+        .map(|layer| quote!(ts.set_layer(|route| route.layer(#layer()));));
+
+    let add_guards = args.guards.iter().map(|guard| {
+        let name = guard.segments.last().unwrap().ident.to_string();
+        quote!(ts.add_guard(#name, |context| context.downcast_ref().map(#guard));)
+    });
+
+    let add_response_headers = args
+        .response_headers
+        .iter()
+        .map(|header| quote!(ts.add_response_header(#header);));
 
     let mut input_types = Vec::new();
     let mut input_type_names = Vec::new();
-    for i in &signature.inputs {
+    let mut input_docs = Vec::new();
+    let mut input_sensitive = Vec::new();
+    let mut input_sources = Vec::new();
+    for i in sig.inputs.iter_mut() {
         match i {
+            syn::FnArg::Receiver(receiver)
+                if is_method && receiver.reference.is_some() && receiver.mutability.is_none() => {}
+            syn::FnArg::Receiver(_) if is_method => {
+                return Err(syn::Error::new_spanned(
+                    &sig.ident,
+                    "service methods must take `&self`",
+                ));
+            }
             syn::FnArg::Receiver(_) => {
                 return Err(syn::Error::new_spanned(
-                    &f.sig.ident,
-                    "self argument not supported",
+                    &sig.ident,
+                    "self argument not supported, use `#[service]` on the impl block",
                 ));
             }
             syn::FnArg::Typed(typed) => {
                 input_types.push((*(typed.ty)).clone());
                 input_type_names.push((*(typed.pat)).clone());
+                // Doc comments aren't allowed on parameters and `#[sensitive]` is ours,
+                // so they are removed here.
+                input_docs.push(docs(&typed.attrs));
+                input_sensitive.push(typed.attrs.iter().any(|a| a.path().is_ident("sensitive")));
+                input_sources.push(param_source(&typed.attrs, &typed.pat)?);
+                typed.attrs.retain(|attr| {
+                    !attr.path().is_ident("doc")
+                        && !attr.path().is_ident("sensitive")
+                        && !SOURCES.iter().any(|source| attr.path().is_ident(source))
+                });
             }
         }
     }
 
-    // removing axum parameter
+    // removing the `Axum<External>` or `Ctx<C>` parameter
     let is_axum = input_types
         .last()
         .map(|ty| {
             if let syn::Type::Path(path) = ty {
                 for segment in path.path.segments.iter() {
-                    if segment.ident == "Axum" {
+                    if segment.ident == "Axum" || segment.ident == "Ctx" {
                         return true;
                     }
                 }
@@ -64,24 +226,75 @@ fn ts_export_inner(_: TokenStream, input: TokenStream) -> Result<TokenStream, sy
     if is_axum {
         input_types.pop();
         input_type_names.pop();
+        input_docs.pop();
+        input_sensitive.pop();
+        input_sources.pop();
     }
 
-    let output = match &signature.output {
-        syn::ReturnType::Default => quote!(()),
-        syn::ReturnType::Type(_, ty) => quote!(#ty),
-    };
+    let set_axum_extractor = is_axum.then(|| quote!(ts.set_axum_extractor();));
+    let set_docs = fn_docs.map(|docs| quote!(ts.set_docs(#docs);));
+    let set_param_docs = input_type_names
+        .iter()
+        .zip(input_docs)
+        .filter_map(|(name, docs)| {
+            docs.map(|docs| quote!(ts.set_param_docs(stringify!(#name), #docs);))
+        });
+
+    // `Upload` parameters are sent as files instead of json, see `ParamType`
+    let add_params = input_types
+        .iter()
+        .zip(&input_type_names)
+        .map(|(ty, name)| quote!(ts.add_param::<#ty>(stringify!(#name));));
+
+    let set_sensitive = input_type_names
+        .iter()
+        .zip(input_sensitive)
+        .filter(|(_, sensitive)| *sensitive)
+        .map(|(name, _)| quote!(ts.set_sensitive(stringify!(#name));));
 
     let this_crate = get_crate_name("ts_rpc", false);
 
-    Ok(quote!(
-        #f
+    let set_param_sources = input_type_names
+        .iter()
+        .zip(input_sources)
+        .filter_map(|(name, source)| {
+            let (variant, source_name) = source?;
+            Some(quote!(
+                ts.set_param_source(
+                    stringify!(#name),
+                    #this_crate::ParamSource::#variant(#source_name),
+                );
+            ))
+        });
 
+    // a `Download` is sent as a file instead of json, `WithHeaders<T>` as `T`
+    let set_response_type = match &sig.output {
+        syn::ReturnType::Default => quote!(ts.set_response_type::<()>();),
+        syn::ReturnType::Type(_, ty) => match without_headers(ty) {
+            ty if is_named(&ty, "Download") => quote!(ts.set_download_response();),
+            ty => match download_error(&ty) {
+                Some(err) => quote!(ts.set_download_result_response::<#err>();),
+                None => quote!(ts.set_response_type::<#ty>();),
+            },
+        },
+    };
+
+    Ok(quote!(
         static #static_name: #this_crate::once_cell::sync::Lazy<#this_crate::TsFn> = #this_crate::once_cell::sync::Lazy::new(|| {
-            let mut ts = #this_crate::TsFn::new(stringify!(#name));
-            #(
-                ts.add_request_type::<#input_types>(stringify!(#input_type_names));
-            )*
-            ts.set_response_type::<#output>();
+            let mut ts = #this_crate::TsFn::new(#name);
+            ts.set_module_path(module_path!());
+            #set_docs
+            #set_deprecated
+            #set_sunset
+            #set_axum_extractor
+            #set_layer
+            #(#add_guards)*
+            #(#add_response_headers)*
+            #(#add_params)*
+            #(#set_param_docs)*
+            #(#set_sensitive)*
+            #(#set_param_sources)*
+            #set_response_type
             ts
         });
         #this_crate::inventory::submit! {
@@ -90,6 +303,788 @@ fn ts_export_inner(_: TokenStream, input: TokenStream) -> Result<TokenStream, sy
     ))
 }
 
+fn service_inner(attr: TokenStream, input: TokenStream) -> Result<TokenStream, syn::Error> {
+    let mut name = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+            Ok(())
+        } else {
+            Err(meta.error("unsupported service argument"))
+        }
+    });
+    syn::parse::Parser::parse2(parser, attr)?;
+
+    let mut item = syn::parse2::<syn::ItemImpl>(input)?;
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "Generics not supported",
+        ));
+    }
+    let self_ty = &item.self_ty;
+    let type_path = match &**self_ty {
+        syn::Type::Path(path) => &path.path,
+        _ => return Err(syn::Error::new_spanned(self_ty, "expected a struct")),
+    };
+    let type_name = type_path.segments.last().unwrap().ident.to_string();
+    // Trait impls are named after the trait, so every implementation has the same api.
+    let service_name = name.unwrap_or_else(|| {
+        let trait_name = item
+            .trait_
+            .as_ref()
+            .map(|(_, path, _)| path.segments.last().unwrap().ident.to_string());
+        snake_case(&trait_name.unwrap_or_else(|| type_name.clone()))
+    });
+    // `<Type>::method` or `<Type as Trait>::method`
+    let qualified_self = match &item.trait_ {
+        Some((_, path, _)) => quote!(<#self_ty as #path>),
+        None => quote!(<#self_ty>),
+    };
+
+    // Names the statics of the methods after the impl, so they don't collide with the statics
+    // of other impls in the module, like the inherent impl and a trait impl of a type.
+    let path_name = |path: &syn::Path| {
+        path.segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("_")
+    };
+    let impl_name = match &item.trait_ {
+        Some((_, path, _)) => format!("{}_as_{}", path_name(type_path), path_name(path)),
+        None => path_name(type_path),
+    };
+
+    let this_crate = get_crate_name("ts_rpc", false);
+    let mut ts_fns = Vec::new();
+    let mut registrations = Vec::new();
+    for impl_item in item.items.iter_mut() {
+        let syn::ImplItem::Fn(method) = impl_item else {
+            continue;
+        };
+        // `#[ts_export(..)]` on a method takes the arguments of `#[ts_export]`
+        let mut args = ExportArgs::default();
+        for attr in method.attrs.iter().filter(|a| a.path().is_ident("ts_export")) {
+            if let syn::Meta::List(list) = &attr.meta {
+                args = ExportArgs::parse(list.tokens.clone())?;
+            }
+        }
+        method.attrs.retain(|a| !a.path().is_ident("ts_export"));
+        if !matches!(method.sig.inputs.first(), Some(syn::FnArg::Receiver(_))) {
+            return Err(syn::Error::new_spanned(
+                &method.sig.ident,
+                "service methods must take `&self`",
+            ));
+        }
+
+        let method_name = method.sig.ident.clone();
+        let fn_name = format!("{service_name}.{method_name}");
+        let static_name = Ident::new(
+            &format!("__{impl_name}_{method_name}"),
+            Span::call_site(),
+        );
+        ts_fns.push(ts_fn(
+            args,
+            &method.attrs,
+            &mut method.sig,
+            &fn_name,
+            &static_name,
+            true,
+        )?);
+
+        let param_types: Vec<_> = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(typed) => Some(&typed.ty),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let params: Vec<_> = (0..param_types.len())
+            .map(|i| Ident::new(&format!("p{i}"), Span::call_site()))
+            .collect();
+        registrations.push(quote!(
+            let api = {
+                let this = this.clone();
+                api.register_procedure(&#static_name, move |#(#params: #param_types),*| {
+                    let this = this.clone();
+                    async move { #qualified_self::#method_name(&this, #(#params),*).await }
+                })
+            };
+        ));
+    }
+
+    Ok(quote!(
+        #item
+
+        #(#ts_fns)*
+
+        impl #this_crate::Service for #self_ty {
+            // calling `#[deprecated]` methods doesn't warn
+            #[allow(deprecated)]
+            fn register(self, api: #this_crate::Api) -> #this_crate::Api {
+                let this = ::std::sync::Arc::new(self);
+                #(#registrations)*
+                api
+            }
+        }
+    ))
+}
+
+// parameter attributes reading the value from the request instead of the arguments
+const SOURCES: [&str; 3] = ["header", "cookie", "query"];
+
+/// The `ParamSource` variant and name of a `#[header]`, `#[cookie]` or `#[query]` parameter.
+/// Without a name, headers are named after the parameter with dashes and the others after
+/// the parameter.
+fn param_source(
+    attrs: &[syn::Attribute],
+    pat: &syn::Pat,
+) -> Result<Option<(Ident, String)>, syn::Error> {
+    let mut sources = attrs
+        .iter()
+        .filter(|attr| SOURCES.iter().any(|source| attr.path().is_ident(source)));
+    let Some(attr) = sources.next() else {
+        return Ok(None);
+    };
+    if let Some(other) = sources.next() {
+        return Err(syn::Error::new_spanned(
+            other,
+            "a parameter is read from one of `#[header]`, `#[cookie]` or `#[query]`",
+        ));
+    }
+    let kind = attr.path().get_ident().unwrap().to_string();
+    let name = match &attr.meta {
+        syn::Meta::Path(_) => {
+            let syn::Pat::Ident(ident) = pat else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("`#[{kind}]` needs a name here, e.g. `#[{kind}(\"name\")]`"),
+                ));
+            };
+            let name = syn::ext::IdentExt::unraw(&ident.ident).to_string();
+            match kind.as_str() {
+                "header" => name.replace('_', "-"),
+                _ => name,
+            }
+        }
+        _ => attr.parse_args::<syn::LitStr>()?.value(),
+    };
+    let mut variant = kind;
+    variant[..1].make_ascii_uppercase();
+    Ok(Some((Ident::new(&variant, Span::call_site()), name)))
+}
+
+/// `ty` with every `WithHeaders<T>` in it replaced by `T`.
+fn without_headers(ty: &syn::Type) -> syn::Type {
+    let mut ty = ty.clone();
+    if let syn::Type::Path(path) = &mut ty {
+        let Some(last) = path.path.segments.last_mut() else {
+            return ty;
+        };
+        if let syn::PathArguments::AngleBracketed(generics) = &mut last.arguments {
+            for arg in generics.args.iter_mut() {
+                if let syn::GenericArgument::Type(inner) = arg {
+                    *inner = without_headers(inner);
+                }
+            }
+            if last.ident == "WithHeaders" {
+                if let Some(syn::GenericArgument::Type(inner)) = generics.args.first() {
+                    return inner.clone();
+                }
+            }
+        }
+    }
+    ty
+}
+
+/// `E` of `Result<Download, E>`.
+fn download_error(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    let syn::PathArguments::AngleBracketed(generics) = &last.arguments else {
+        return None;
+    };
+    match (generics.args.first(), generics.args.get(1)) {
+        (Some(syn::GenericArgument::Type(ok)), Some(syn::GenericArgument::Type(err)))
+            if last.ident == "Result" && is_named(ok, "Download") =>
+        {
+            Some(err)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `ty` is a path ending in `name`, e.g. `ts_rpc::Download` for `Download`.
+fn is_named(ty: &syn::Type, name: &str) -> bool {
+    matches!(
+        ty,
+        syn::Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == name)
+    )
+}
+
+/// `UserService` -> `user_service`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn ts_docs_inner(input: TokenStream) -> Result<TokenStream, syn::Error> {
+    let item = syn::parse2::<syn::DeriveInput>(input)?;
+
+    let container_rename = serde_attr(&item.attrs, "rename");
+    let rename_all = serde_attr(&item.attrs, "rename_all");
+    let ts_name = container_rename.unwrap_or_else(|| item.ident.to_string());
+    let type_docs = match docs(&item.attrs) {
+        Some(docs) => quote!(Some(#docs)),
+        None => quote!(None),
+    };
+
+    let mut field_names = Vec::new();
+    let mut field_docs = Vec::new();
+    if let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &item.data
+    {
+        for field in fields.named.iter() {
+            let Some(docs) = docs(&field.attrs) else {
+                continue;
+            };
+            field_names.push(serialized_name(field, &rename_all));
+            field_docs.push(docs);
+        }
+    }
+
+    let this_crate = get_crate_name("ts_rpc", false);
+
+    Ok(quote!(
+        #this_crate::inventory::submit! {
+            #this_crate::TypeDocs {
+                name: #ts_name,
+                docs: #type_docs,
+                fields: &[#((#field_names, #field_docs)),*],
+            }
+        }
+    ))
+}
+
+/// Joins all `///` comments of `attrs`, removing the space following `///`.
+fn docs(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(s),
+                        ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => Some(s.value()),
+            _ => None,
+        })
+        .map(|line| {
+            line.strip_prefix(' ')
+                .unwrap_or(&line)
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    let docs = lines.join("\n").trim().to_string();
+    (!docs.is_empty()).then_some(docs)
+}
+
+/// The note and since of a `#[deprecated]` attribute.
+type Deprecation = (Option<String>, Option<String>);
+
+fn deprecation(attrs: &[syn::Attribute]) -> Result<Option<Deprecation>, syn::Error> {
+    let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("deprecated")) else {
+        return Ok(None);
+    };
+    let mut note = None;
+    let mut since = None;
+    match &attr.meta {
+        syn::Meta::Path(_) => {}
+        syn::Meta::NameValue(syn::MetaNameValue {
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }),
+            ..
+        }) => note = Some(s.value()),
+        syn::Meta::NameValue(nv) => {
+            return Err(syn::Error::new_spanned(nv, "expected a string literal"));
+        }
+        syn::Meta::List(_) => attr.parse_nested_meta(|meta| {
+            let value = meta.value()?.parse::<syn::LitStr>()?.value();
+            if meta.path.is_ident("note") {
+                note = Some(value);
+            } else if meta.path.is_ident("since") {
+                since = Some(value);
+            }
+            Ok(())
+        })?,
+    }
+    Ok(Some((note, since)))
+}
+
+/// The name of a field in json and typescript.
+fn serialized_name(field: &syn::Field, rename_all: &Option<String>) -> String {
+    serde_attr(&field.attrs, "rename").unwrap_or_else(|| {
+        let name = field.ident.as_ref().unwrap().to_string();
+        let name = name.strip_prefix("r#").unwrap_or(&name).to_string();
+        match rename_all {
+            Some(rule) => rename(&name, rule),
+            None => name,
+        }
+    })
+}
+
+fn validate_inner(input: TokenStream) -> Result<TokenStream, syn::Error> {
+    let item = syn::parse2::<syn::DeriveInput>(input)?;
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &item.data
+    else {
+        return Err(syn::Error::new_spanned(
+            &item.ident,
+            "`Validate` can only be derived for structs with named fields",
+        ));
+    };
+
+    let this_crate = get_crate_name("ts_rpc", false);
+    let rename_all = serde_attr(&item.attrs, "rename_all");
+    let ts_name = serde_attr(&item.attrs, "rename").unwrap_or_else(|| item.ident.to_string());
+
+    let mut checks = Vec::new();
+    // serialized field name -> constraints, exported to typescript
+    let mut field_constraints = Vec::new();
+    for field in fields.named.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let name = serialized_name(field, &rename_all);
+        let mut constraints = Vec::new();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("validate")) {
+            attr.parse_nested_meta(|meta| {
+                let constraint = if meta.path.is_ident("email") {
+                    quote!(#this_crate::Constraint::Email)
+                } else if meta.path.is_ident("url") {
+                    quote!(#this_crate::Constraint::Url)
+                } else if meta.path.is_ident("length") {
+                    let (min, max) = bounds(&meta, false)?;
+                    quote!(#this_crate::Constraint::Length { min: #min, max: #max })
+                } else if meta.path.is_ident("range") {
+                    let (min, max) = bounds(&meta, true)?;
+                    quote!(#this_crate::Constraint::Range { min: #min, max: #max })
+                } else if meta.path.is_ident("nested") {
+                    checks.push(quote!(
+                        #this_crate::validate::nested(&mut errors, #name, &self.#ident);
+                    ));
+                    return Ok(());
+                } else {
+                    return Err(meta.error(
+                        "expected `email`, `url`, `length(..)`, `range(..)` or `nested`",
+                    ));
+                };
+                checks.push(quote!(
+                    #this_crate::validate::check(&mut errors, #name, &self.#ident, #constraint);
+                ));
+                constraints.push(constraint);
+                Ok(())
+            })?;
+        }
+        if !constraints.is_empty() {
+            field_constraints.push(quote!((#name, &[#(#constraints),*])));
+        }
+    }
+
+    let ident = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    // Generic types can't be looked up by their type id, so they are only validated nested.
+    let submit = item.generics.params.is_empty().then(|| {
+        quote!(
+            #this_crate::inventory::submit! {
+                #this_crate::TypeConstraints {
+                    name: #ts_name,
+                    id: #this_crate::validate::ts_id::<#ident>,
+                    validators: #this_crate::validate::validators::<#ident>,
+                    fields: &[#(#field_constraints),*],
+                }
+            }
+        )
+    });
+
+    Ok(quote!(
+        impl #impl_generics #this_crate::Validate for #ident #ty_generics #where_clause {
+            fn validate(&self) -> ::std::result::Result<(), ::std::vec::Vec<#this_crate::FieldError>> {
+                let mut errors = ::std::vec::Vec::new();
+                #(#checks)*
+                if errors.is_empty() {
+                    ::std::result::Result::Ok(())
+                } else {
+                    ::std::result::Result::Err(errors)
+                }
+            }
+        }
+
+        #submit
+    ))
+}
+
+/// The `min` and `max` of `length(..)` as `usize`s or `range(..)` as `f64`s, which may be
+/// negative. At least one of them is set.
+fn bounds(
+    meta: &syn::meta::ParseNestedMeta,
+    range: bool,
+) -> Result<(TokenStream, TokenStream), syn::Error> {
+    let mut min = None;
+    let mut max = None;
+    meta.parse_nested_meta(|bound| {
+        let input = bound.value()?;
+        let negative = input.parse::<Option<syn::Token![-]>>()?.is_some();
+        let value = match input.parse::<syn::Lit>()? {
+            syn::Lit::Int(lit) if !range && !negative => {
+                let n = lit.base10_parse::<usize>()?;
+                quote!(#n)
+            }
+            syn::Lit::Int(lit) if range => {
+                let n = lit.base10_parse::<f64>()? * if negative { -1.0 } else { 1.0 };
+                quote!(#n)
+            }
+            syn::Lit::Float(lit) if range => {
+                let n = lit.base10_parse::<f64>()? * if negative { -1.0 } else { 1.0 };
+                quote!(#n)
+            }
+            lit if range => return Err(syn::Error::new_spanned(lit, "expected a number")),
+            lit => return Err(syn::Error::new_spanned(lit, "expected a length")),
+        };
+        if bound.path.is_ident("min") {
+            min = Some(value);
+        } else if bound.path.is_ident("max") {
+            max = Some(value);
+        } else {
+            return Err(bound.error("expected `min` or `max`"));
+        }
+        Ok(())
+    })?;
+    if min.is_none() && max.is_none() {
+        return Err(meta.error("expected `min` and/or `max`"));
+    }
+    let option = |bound: Option<TokenStream>| match bound {
+        Some(bound) => quote!(Some(#bound)),
+        None => quote!(None),
+    };
+    Ok((option(min), option(max)))
+}
+
+/// Finds `key = "value"` in `#[serde(..)]` or `#[ts(..)]` attributes.
+fn serde_attr(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    let mut value = None;
+    for attr in attrs {
+        if !attr.path().is_ident("serde") && !attr.path().is_ident("ts") {
+            continue;
+        }
+        // Other attributes use syntax we don't care about, so errors are ignored.
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                if let Ok(s) = meta.value()?.parse::<syn::LitStr>() {
+                    value = Some(s.value());
+                }
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|_| Ok(()))?;
+            }
+            Ok(())
+        });
+    }
+    value
+}
+
+/// Applies a serde `rename_all` rule to a snake case field name.
+fn rename(name: &str, rule: &str) -> String {
+    let words = name.split('_').filter(|w| !w.is_empty());
+    let capitalize = |w: &str| {
+        let mut chars = w.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+            .unwrap_or_default()
+    };
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "PascalCase" => words.map(capitalize).collect(),
+        "camelCase" => words
+            .enumerate()
+            .map(|(i, w)| if i == 0 { w.to_string() } else { capitalize(w) })
+            .collect(),
+        "SCREAMING_SNAKE_CASE" => name.to_uppercase(),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+#[test]
+fn test_docs() {
+    let input = quote! {
+        /// Logs a user in.
+        ///
+        /// Returns a session token.
+        async fn login(
+            /// The email address.
+            email: String,
+            #[sensitive]
+            password: String,
+        ) -> String {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input)
+        .unwrap()
+        .to_string();
+    assert!(output.contains("ts . set_module_path (module_path ! ()) ;"));
+    assert!(output.contains("impl ts_rpc :: ProcedureMarker for __login_marker"));
+    assert!(output.contains("type_id (& login)"));
+    assert!(output.contains("ts_rpc :: Procedure :: of :: < __login_marker > ()"));
+    assert!(output.contains("ts . set_sensitive (stringify ! (password))"));
+    assert!(!output.contains("# [sensitive]"));
+    assert!(output.contains("ts . set_docs (\"Logs a user in.\\n\\nReturns a session token.\")"));
+    assert!(output.contains("ts . set_param_docs (stringify ! (email) , \"The email address.\")"));
+    assert!(!output.contains("# [doc = \" The email address.\"]"));
+
+    let input = quote! {
+        /// A user.
+        #[serde(rename_all = "camelCase")]
+        struct User {
+            /// The name.
+            display_name: String,
+            age: u32,
+        }
+    };
+    let output = ts_docs_inner(input).unwrap().to_string();
+    assert!(output.contains("name : \"User\""));
+    assert!(output.contains("fields : & [(\"displayName\" , \"The name.\")]"));
+}
+
+#[test]
+fn test_deprecated() {
+    let input = quote! {
+        #[deprecated(since = "0.2.0", note = "Use `sign_out` instead.")]
+        async fn logout() {}
+    };
+    let attr = quote!(sunset = "Fri, 01 Jan 2027 00:00:00 GMT");
+    let output = ts_export_inner(attr, input).unwrap().to_string();
+    assert!(output
+        .contains("ts . set_deprecated (Some (\"Use `sign_out` instead.\") , Some (\"0.2.0\")) ;"));
+    assert!(output.contains("ts . set_sunset (\"Fri, 01 Jan 2027 00:00:00 GMT\") ;"));
+
+    let input = quote! {
+        async fn logout() {}
+    };
+    let attr = quote!(sunset = "Fri, 01 Jan 2027 00:00:00 GMT");
+    assert!(ts_export_inner(attr, input).is_err());
+}
+
+#[test]
+fn test_layer() {
+    let input = quote! {
+        async fn login(email: String) -> String {}
+    };
+    let attr = quote!(layer = limits::login);
+    let output = ts_export_inner(attr, input).unwrap().to_string();
+    assert!(output.contains("ts . set_layer (| route | route . layer (limits :: login ())) ;"));
+}
+
+#[test]
+fn test_guard() {
+    let input = quote! {
+        async fn ban_user(id: u32, ctx: Ctx<User>) {}
+    };
+    let attr = quote!(guard = auth::require_admin, guard = not_banned);
+    let output = ts_export_inner(attr, input).unwrap().to_string();
+    assert!(output.contains(
+        "ts . add_guard (\"require_admin\" , | context | context . downcast_ref () . map (auth :: require_admin)) ;"
+    ));
+    assert!(output.contains("ts . add_guard (\"not_banned\""));
+    assert!(output.contains("ts . set_axum_extractor () ;"));
+    assert!(!output.contains("add_param :: < Ctx < User > >"));
+}
+
+#[test]
+fn test_upload() {
+    let input = quote! {
+        async fn upload_avatar(user_id: u32, avatar: ts_rpc::Upload) {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . add_param :: < u32 > (stringify ! (user_id)) ;"));
+    assert!(output.contains("ts . add_param :: < ts_rpc :: Upload > (stringify ! (avatar)) ;"));
+}
+
+#[test]
+fn test_param_sources() {
+    let input = quote! {
+        async fn list_orders(
+            #[header("x-tenant-id")] tenant: String,
+            #[cookie("session")] session: Option<String>,
+            #[query] page: u32,
+            #[header] user_agent: Option<String>,
+            status: String,
+        ) {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . add_param :: < String > (stringify ! (tenant)) ;"));
+    assert!(output.contains(
+        "ts . set_param_source (stringify ! (tenant) , ts_rpc :: ParamSource :: Header (\"x-tenant-id\") ,) ;"
+    ));
+    assert!(output.contains("ts_rpc :: ParamSource :: Cookie (\"session\")"));
+    assert!(output.contains("ts_rpc :: ParamSource :: Query (\"page\")"));
+    assert!(output.contains("ts_rpc :: ParamSource :: Header (\"user-agent\")"));
+    assert!(!output.contains("set_param_source (stringify ! (status)"));
+    assert!(!output.contains("# [header"));
+    assert!(!output.contains("# [query"));
+
+    let input = quote! {
+        async fn list_orders(#[header] #[query] tenant: String) {}
+    };
+    assert!(ts_export_inner(TokenStream::new(), input).is_err());
+}
+
+#[test]
+fn test_download() {
+    let input = quote! {
+        async fn export_invoices(year: u32) -> Download {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . set_download_response () ;"));
+    assert!(!output.contains("set_response_type"));
+
+    let input = quote! {
+        async fn export_invoices(year: u32) -> Result<WithHeaders<Download>, String> {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . set_download_result_response :: < String > () ;"));
+}
+
+#[test]
+fn test_with_headers() {
+    let input = quote! {
+        async fn list_orders(page: u32) -> WithHeaders<Vec<Order>> {}
+    };
+    let attr = quote!(response_header = "x-total-count", response_header = "link");
+    let output = ts_export_inner(attr, input).unwrap().to_string();
+    assert!(output.contains("ts . set_response_type :: < Vec < Order > > () ;"));
+    assert!(output.contains("ts . add_response_header (\"x-total-count\") ;"));
+    assert!(output.contains("ts . add_response_header (\"link\") ;"));
+
+    let input = quote! {
+        async fn login(email: String) -> Result<ts_rpc::WithHeaders<User>, String> {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . set_response_type :: < Result < User , String > > () ;"));
+
+    let input = quote! {
+        async fn export_invoices(year: u32) -> WithHeaders<Download> {}
+    };
+    let output = ts_export_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("ts . set_download_response () ;"));
+}
+
+#[test]
+fn test_validate() {
+    let input = quote! {
+        #[serde(rename_all = "camelCase")]
+        struct SignUp {
+            #[validate(email, length(max = 100))]
+            email_address: String,
+            #[validate(range(min = -1, max = 2.5))]
+            score: f32,
+            #[validate(nested)]
+            address: Option<Address>,
+            name: String,
+        }
+    };
+    let output = validate_inner(input).unwrap().to_string();
+    assert!(output.contains(
+        "ts_rpc :: validate :: check (& mut errors , \"emailAddress\" , & self . email_address , ts_rpc :: Constraint :: Email) ;"
+    ));
+    assert!(output.contains(
+        "ts_rpc :: Constraint :: Length { min : None , max : Some (100usize) }"
+    ));
+    assert!(output.contains(
+        "ts_rpc :: Constraint :: Range { min : Some (- 1f64) , max : Some (2.5f64) }"
+    ));
+    assert!(output.contains(
+        "ts_rpc :: validate :: nested (& mut errors , \"address\" , & self . address) ;"
+    ));
+    assert!(output.contains("name : \"SignUp\""));
+    assert!(!output.contains("\"name\""));
+
+    let input = quote! {
+        struct SignUp {
+            #[validate(length(min = -1))]
+            name: String,
+        }
+    };
+    assert!(validate_inner(input).is_err());
+    let input = quote! {
+        struct SignUp(#[validate(email)] String);
+    };
+    assert!(validate_inner(input).is_err());
+}
+
+#[test]
+fn test_service() {
+    let input = quote! {
+        impl UserService {
+            /// Gets a user.
+            async fn get(&self, id: u32) -> User {}
+
+            #[ts_export(guard = require_admin)]
+            async fn ban(&self, id: u32, ctx: Ctx<Admin>) {}
+        }
+    };
+    let output = service_inner(TokenStream::new(), input).unwrap().to_string();
+    assert!(output.contains("TsFn :: new (\"user_service.get\")"));
+    assert!(output.contains("ts . add_guard (\"require_admin\""));
+    assert!(!output.contains("# [ts_export"));
+    assert!(output.contains(
+        "api . register_procedure (& __UserService_ban , move | p0 : u32 , p1 : Ctx < Admin > |"
+    ));
+    assert!(!output.contains("ProcedureMarker"));
+    assert!(output.contains("# [allow (deprecated)] fn register (self"));
+    assert!(output.contains("< UserService > :: ban (& this , p0 , p1) . await"));
+
+    let input = quote! {
+        impl Users for Postgres {
+            async fn get(&self, id: u32) -> User {}
+        }
+    };
+    let output = service_inner(quote!(name = "users"), input).unwrap().to_string();
+    assert!(output.contains("TsFn :: new (\"users.get\")"));
+    assert!(output.contains("register_procedure (& __Postgres_as_Users_get ,"));
+    assert!(output.contains("< Postgres as Users > :: get (& this , p0) . await"));
+
+    let input = quote! {
+        impl Users {
+            async fn get(id: u32) -> User {}
+        }
+    };
+    assert!(service_inner(TokenStream::new(), input).is_err());
+}
+
 #[test]
 fn test() {
     let input = quote! {